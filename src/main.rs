@@ -1,14 +1,18 @@
 mod smphr;
 
-fn main() {
-    match smphr::SmphrParams::from_args() {
-        Ok(p) => {
-            if let Err(e) = smphr::exec(p) {
-                println!("{}", e);
-            }
-        },
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let done = match smphr::Command::from_args() {
+        Ok(smphr::Command::Render(p)) => smphr::exec(*p)
+            .map_err(|e| eprintln!("{}", e)).is_ok(),
+        Ok(smphr::Command::Batch(jobs)) => smphr::exec_batch(jobs),
+        Ok(smphr::Command::Inspect(path)) => smphr::inspect(&path)
+            .map_err(|e| eprintln!("{}", e)).is_ok(),
         Err(e) => {
-            println!("Error occured: {}", e)
+            eprintln!("Error occured: {}", e);
+            false
         }
-    }
+    };
+    if done { ExitCode::SUCCESS } else { ExitCode::FAILURE }
 }
@@ -0,0 +1,479 @@
+use super::stickman::StickmanError;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Highest arm position, positions go by steps of 45 degrees.
+pub static MAX_POSITION: u8 = 7;
+
+static LATIN: &str = include_str!("alphabets/latin.toml");
+static KANA: &str = include_str!("alphabets/ja.toml");
+
+/// Alphabets shipped with smphr, by name.
+pub static BUILTINS: &[(&str, &str)] = &[("latin", LATIN), ("ja", KANA)];
+
+/// Positions of both arms for a sign.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Pose {
+    pub right: u8,
+    pub left:  u8,
+}
+
+impl Pose {
+    pub fn from(right: u8, left: u8) -> Pose {
+        Pose {
+            right,
+            left,
+        }
+    }
+
+    fn is_reachable(&self) -> bool {
+        self.right <= MAX_POSITION && self.left <= MAX_POSITION
+    }
+}
+
+impl std::fmt::Display for Pose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}, {}]", self.right, self.left)
+    }
+}
+
+/// What has to be signalled for a piece of text.
+#[derive(Clone, Debug)]
+pub enum Sign {
+    Pose(Pose),
+    /// Several poses signalled one after the other for a single character.
+    Strokes(Vec<Pose>),
+    Space,
+    NewLine,
+}
+
+impl Sign {
+    /// Number of poses needed to signal it.
+    pub fn strokes(&self) -> usize {
+        match self {
+            Sign::Strokes(v) => v.len(),
+            _ => 1,
+        }
+    }
+
+    /// The sign as seen at the k-th step of an animation, a sign with fewer
+    /// strokes holding its last pose.
+    pub fn stroke(&self, k: usize) -> Sign {
+        match self {
+            Sign::Strokes(v) => Sign::Pose(v[k.min(v.len() - 1)]),
+            _ => self.clone(),
+        }
+    }
+
+    fn from_poses(poses: &[Pose]) -> Sign {
+        match poses {
+            [p] => Sign::Pose(*p),
+            _ => Sign::Strokes(poses.to_vec()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AlphabetError {
+    CouldNotRead(std::io::Error),
+    UnknownFormat(String),
+    UnknownAlias(char, char),
+    EmptySign(char),
+    Parse(String),
+    NoMode,
+    InvalidKey(String),
+    Unreachable(char, Pose),
+    UnreachableSwitch(String, Pose),
+    DuplicatePose(char, char),
+    AmbiguousSwitch(String, char),
+}
+
+impl std::fmt::Display for AlphabetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CouldNotRead(e) => write!(f, "could not read alphabet: {}", e),
+            Self::UnknownFormat(p) => write!(f, "unknown alphabet format for \
+'{}', expected .toml or .json", p),
+            Self::UnknownAlias(c, k) => write!(f, "'{}' is an alias of \
+'{}', which has no sign", c, k),
+            Self::EmptySign(c) => write!(f, "no pose given for '{}'", c),
+            Self::Parse(e) => write!(f, "could not parse alphabet: {}", e),
+            Self::NoMode => write!(f, "alphabet defines no mode"),
+            Self::InvalidKey(k) => write!(f, "'{}' is not a single \
+character", k),
+            Self::Unreachable(c, p) => write!(f, "unreachable position {} \
+for '{}'", p, c),
+            Self::UnreachableSwitch(m, p) => write!(f, "unreachable position \
+{} for the switch to mode '{}'", p, m),
+            Self::DuplicatePose(a, b) => write!(f, "'{}' and '{}' share \
+the same sign", a, b),
+            Self::AmbiguousSwitch(m, c) => write!(f, "switch to mode '{}' \
+has the same pose as '{}'", m, c),
+        }
+    }
+}
+
+/* On-disk representation, validated into an Alphabet. */
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AlphabetDef {
+    /// Name the alphabet goes by, that of its file when left out.
+    #[serde(default)]
+    name:  Option<String>,
+    modes: Vec<ModeDef>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModeDef {
+    name:   String,
+    switch:  Option<[u8; 2]>,
+    signs:   HashMap<String, SignDef>,
+    /// Characters signalled with the signs of other ones, e.g. `ё` as `е`.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/* Either a single pose or the sequence of poses spelling a character. */
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SignDef {
+    One([u8; 2]),
+    Strokes(Vec<[u8; 2]>),
+}
+
+impl SignDef {
+    fn poses(self) -> Vec<Pose> {
+        match self {
+            SignDef::One([r, l]) => vec![Pose::from(r, l)],
+            SignDef::Strokes(v) => v.into_iter()
+                                    .map(|[r, l]| Pose::from(r, l))
+                                    .collect(),
+        }
+    }
+}
+
+/// A set of signs that are read the same way until the next mode switch,
+/// e.g. letters or numerals.
+#[derive(Debug)]
+pub struct Mode {
+    pub name:   String,
+    /// Sign telling the reader that the following signs belong to this mode.
+    pub switch: Option<Pose>,
+    signs:      HashMap<char, Vec<Pose>>,
+}
+
+impl Mode {
+    fn get(&self, c: char) -> Option<&[Pose]> {
+        if let Some(p) = self.signs.get(&c) {
+            return Some(p);
+        }
+        let mut lower = c.to_lowercase();
+        match (lower.next(), lower.next()) {
+            (Some(l), None) if l != c => self.signs.get(&l)
+                                             .map(|v| v.as_slice()),
+            _ => None,
+        }
+    }
+}
+
+/// Mapping from characters to poses. The first mode is the one in use at
+/// the beginning of a message.
+#[derive(Debug)]
+pub struct Alphabet {
+    /// Name given in its definition, or file it was loaded from.
+    name: String,
+    modes: Vec<Mode>,
+}
+
+impl Alphabet {
+    /// The international semaphore with latin letters and numerals.
+    pub fn latin() -> Alphabet {
+        Alphabet::builtin("latin").expect("listed in BUILTINS")
+    }
+
+    /// Looks up a built-in alphabet by name.
+    pub fn builtin(name: &str) -> Option<Alphabet> {
+        BUILTINS.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, def)| Alphabet::from_toml(def)
+                                         .expect("built-in alphabet is valid"))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Picks the built-in alphabet able to signal most of the text, so that
+    /// the alphabet follows the script the text is written in.
+    pub fn detect(text: &str) -> Alphabet {
+        let mut best = Alphabet::latin();
+        let mut best_count = best.count_known(text);
+        for (name, _) in BUILTINS.iter().skip(1) {
+            let a = Alphabet::builtin(name).expect("listed in BUILTINS");
+            let count = a.count_known(text);
+            if count > best_count {
+                best = a;
+                best_count = count;
+            }
+        }
+        best
+    }
+
+    /// Every character of every mode with its poses, mode after mode.
+    pub fn characters(&self) -> Vec<(&Mode, char, &[Pose])> {
+        let mut all = Vec::new();
+        for mode in self.modes.iter() {
+            let mut chars: Vec<_> = mode.signs.iter().collect();
+            chars.sort_by_key(|(c, _)| **c);
+            all.extend(chars.into_iter()
+                            .map(|(c, poses)| (mode, *c, poses.as_slice())));
+        }
+        all
+    }
+
+    /// Name of the mode a switch sign leads to.
+    pub fn switched_to(&self, pose: Pose) -> Option<&str> {
+        self.modes.iter().find(|m| m.switch == Some(pose))
+            .map(|m| m.name.as_str())
+    }
+
+    /// Largest number of poses spelling a single character.
+    pub fn max_strokes(&self) -> usize {
+        self.modes.iter()
+            .flat_map(|m| m.signs.values().map(|p| p.len()))
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn count_known(&self, text: &str) -> usize {
+        text.chars()
+            .filter(|c| c.is_alphabetic()
+                        && self.modes.iter().any(|m| m.get(*c).is_some()))
+            .count()
+    }
+
+    /// Loads an alphabet from a `.toml` or `.json` definition file.
+    pub fn from_file(path: &str) -> Result<Alphabet, AlphabetError> {
+        let ext = Path::new(path).extension()
+                                 .and_then(|e| e.to_str())
+                                 .map(|e| e.to_ascii_lowercase());
+        let content = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) => return Err(AlphabetError::CouldNotRead(e)),
+        };
+        let mut a = match ext.as_deref() {
+            Some("toml") => Alphabet::from_toml(&content),
+            Some("json") => Alphabet::from_json(&content),
+            _ => Err(AlphabetError::UnknownFormat(path.to_string())),
+        }?;
+        if a.name.is_empty() {
+            a.name = path.to_string();
+        }
+        Ok(a)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Alphabet, AlphabetError> {
+        match toml::from_str::<AlphabetDef>(s) {
+            Ok(def) => Alphabet::from_def(def),
+            Err(e) => Err(AlphabetError::Parse(e.to_string())),
+        }
+    }
+
+    pub fn from_json(s: &str) -> Result<Alphabet, AlphabetError> {
+        match serde_json::from_str::<AlphabetDef>(s) {
+            Ok(def) => Alphabet::from_def(def),
+            Err(e) => Err(AlphabetError::Parse(e.to_string())),
+        }
+    }
+
+    fn from_def(def: AlphabetDef) -> Result<Alphabet, AlphabetError> {
+        if def.modes.is_empty() {
+            return Err(AlphabetError::NoMode);
+        }
+        let name = def.name.unwrap_or_default();
+
+        let mut modes = Vec::with_capacity(def.modes.len());
+        for m in def.modes {
+            let mut signs = HashMap::new();
+            let mut seen: HashMap<Vec<Pose>, char> = HashMap::new();
+            // sorted so that errors do not depend on the hash order
+            let mut entries: Vec<_> = m.signs.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            for (key, def) in entries {
+                let c = single_char(key)?;
+                let poses = def.poses();
+                if poses.is_empty() {
+                    return Err(AlphabetError::EmptySign(c));
+                }
+                if let Some(p) = poses.iter().find(|p| !p.is_reachable()) {
+                    return Err(AlphabetError::Unreachable(c, *p));
+                }
+                if let Some(other) = seen.insert(poses.clone(), c) {
+                    return Err(AlphabetError::DuplicatePose(other, c));
+                }
+                signs.insert(c, poses);
+            }
+
+            let mut aliases: Vec<_> = m.aliases.into_iter().collect();
+            aliases.sort();
+            for (key, target) in aliases {
+                let c = single_char(key)?;
+                let mut poses = Vec::new();
+                for t in target.chars() {
+                    match signs.get(&t) {
+                        Some(p) => poses.extend_from_slice(p),
+                        None => return Err(AlphabetError::UnknownAlias(c, t)),
+                    }
+                }
+                if poses.is_empty() {
+                    return Err(AlphabetError::EmptySign(c));
+                }
+                signs.insert(c, poses);
+            }
+
+            let switch = m.switch.map(|[r, l]| Pose::from(r, l));
+            if let Some(p) = switch {
+                if !p.is_reachable() {
+                    return Err(AlphabetError::UnreachableSwitch(m.name, p));
+                }
+            }
+            modes.push(Mode { name: m.name, switch, signs });
+        }
+
+        // while in a mode, a switch must not be mistaken for one of its signs
+        for (i, m) in modes.iter().enumerate() {
+            let p = match m.switch {
+                Some(p) => p,
+                None => continue,
+            };
+            for (j, other) in modes.iter().enumerate() {
+                if i == j { continue; }
+                if let Some((c, _)) = other.signs.iter()
+                                           .find(|(_, q)| q[..] == [p]) {
+                    return Err(AlphabetError::AmbiguousSwitch(m.name.clone(),
+                                                              *c));
+                }
+            }
+        }
+
+        Ok(Alphabet { name, modes })
+    }
+
+    /// Translates the text into signs, inserting mode switches where needed,
+    /// each with the index of the character it comes from.
+    pub fn encode(&self, text: &str)
+        -> Vec<(usize, Result<Sign, StickmanError>)> {
+        let mut signs = Vec::new();
+        let mut current = 0;
+
+        for (i, c) in text.chars().enumerate() {
+            match c {
+                ' ' => { signs.push((i, Ok(Sign::Space))); continue; },
+                '\n' => { signs.push((i, Ok(Sign::NewLine))); continue; },
+                _ => {}
+            }
+
+            if let Some(p) = self.modes[current].get(c) {
+                signs.push((i, Ok(Sign::from_poses(p))));
+                continue;
+            }
+
+            let found = self.modes.iter().enumerate()
+                            .find_map(|(m, mode)| mode.get(c).map(|p| (m, p)));
+            match found {
+                Some((m, p)) => {
+                    if let Some(s) = self.modes[m].switch {
+                        signs.push((i, Ok(Sign::Pose(s))));
+                    }
+                    current = m;
+                    signs.push((i, Ok(Sign::from_poses(p))));
+                },
+                None => signs.push((i,
+                                    Err(StickmanError::InvalidCharacter(c)))),
+            }
+        }
+
+        signs
+    }
+}
+
+fn single_char(key: String) -> Result<char, AlphabetError> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(AlphabetError::InvalidKey(key)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn poses(a: &Alphabet, text: &str) -> Vec<Pose> {
+        a.encode(text).into_iter().filter_map(|(_, s)| match s {
+            Ok(Sign::Pose(p)) => Some(p),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn latin_switches_to_numerals() {
+        let a = Alphabet::latin();
+        assert_eq!(poses(&a, "A1b"), vec![Pose::from(1, 0), Pose::from(4, 5),
+                                          Pose::from(1, 0), Pose::from(4, 6),
+                                          Pose::from(2, 0)]);
+    }
+
+    #[test]
+    fn detects_the_script() {
+        assert_eq!(Alphabet::detect("がっこう").name(), "ja");
+        // no built-in alphabet signals Cyrillic
+        let a = Alphabet::detect("Привет, мир");
+        assert_eq!(a.name(), "latin");
+        assert!(a.encode("ж")[0].1.is_err());
+    }
+
+    #[test]
+    fn kana_are_spelled_in_strokes() {
+        let a = Alphabet::detect("がっこう");
+        let signs = a.encode("かが");
+        match (&signs[0].1, &signs[1].1) {
+            (Ok(Sign::Strokes(k)), Ok(Sign::Strokes(g))) => {
+                assert_eq!(k[..], g[..k.len()]);
+                assert_eq!(g.len(), k.len() + 1);
+            },
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn rejects_duplicates_and_unreachable() {
+        let dup = r#"{"modes": [{"name": "x",
+                                  "signs": {"a": [1, 0], "b": [1, 0]}}]}"#;
+        match Alphabet::from_json(dup) {
+            Err(AlphabetError::DuplicatePose('a', 'b')) => {},
+            r => panic!("unexpected {:?}", r),
+        }
+
+        let far = "[[modes]]\nname = \"x\"\n[modes.signs]\na = [1, 8]\n";
+        match Alphabet::from_toml(far) {
+            Err(AlphabetError::Unreachable('a', _)) => {},
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let typo = "[[modes]]\nname = \"x\"\n[modes.signs]\na = [1, 0]\n\
+                    [modes.alias]\nb = \"a\"\n";
+        match Alphabet::from_toml(typo) {
+            Err(AlphabetError::Parse(e)) => assert!(e.contains("alias")),
+            r => panic!("unexpected {:?}", r),
+        }
+        for (name, _) in BUILTINS.iter() {
+            assert_eq!(Alphabet::builtin(name).unwrap().name(), *name);
+        }
+    }
+}
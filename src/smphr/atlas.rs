@@ -0,0 +1,207 @@
+use super::{sized_image, SmphrParams};
+use super::alphabet::{Pose, Sign};
+use super::raster::{Buffer, RasterCanvas};
+use super::stickman::{overhang, stick_height, stick_width, Point, Stickman};
+use image::ImageError;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+/* Sprites on a row of a sheet, for a single pose each. */
+static SHEET_COLUMNS: i32 = 16;
+
+/// How the sprites of the characters are written.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpriteMode {
+    /// All in a single image, the atlas next to it.
+    Sheet,
+    /// One image per character in a directory, with the atlas.
+    Files,
+}
+
+#[derive(Serialize)]
+struct Rect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+#[derive(Serialize)]
+struct Anchor {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Frame {
+    char: String,
+    mode: String,
+    poses: Vec<[u8; 2]>,
+    /// Image of the sprite, when each one has its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    /// Rectangle of the sprite on its image.
+    frame: Rect,
+    /// Centre of each figure, from the corner of the frame.
+    anchors: Vec<Anchor>,
+}
+
+#[derive(Serialize)]
+struct Atlas {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    frames: Vec<Frame>,
+}
+
+/// Writes every character of the alphabet as a sprite, its poses side by
+/// side, with a JSON atlas giving the rectangle of each sprite and the centre
+/// of each of its figures.
+pub fn export(params: &SmphrParams, mode: SpriteMode)
+    -> Result<(), ImageError> {
+
+    let (w, h, pad) = (stick_width(params), stick_height(params),
+                       overhang(params));
+    let characters = params.alphabet.characters();
+    let widest = params.alphabet.max_strokes() as i32 * w + 2 * pad;
+
+    // frames are packed on rows as high as a figure, as in the layout
+    let sheet_width = (SHEET_COLUMNS * w + 2 * pad).max(widest);
+    let (mut x, mut y) = (0, 0);
+    let mut frames = Vec::with_capacity(characters.len());
+    for (m, c, poses) in characters.iter() {
+        let fw = poses.len() as i32 * w + 2 * pad;
+        if mode == SpriteMode::Sheet && x + fw > sheet_width {
+            x = 0;
+            y += h + 2 * pad;
+        }
+        let (fx, fy) = if mode == SpriteMode::Sheet { (x, y) } else { (0, 0) };
+        let anchors = (0..poses.len() as i32)
+            .map(|k| Anchor { x: pad + k * w + w / 2, y: pad + h / 2 })
+            .collect();
+        frames.push(Frame {
+            char: c.to_string(),
+            mode: m.name.clone(),
+            poses: poses.iter().map(|p| [p.right, p.left]).collect(),
+            file: None,
+            frame: Rect { x: fx, y: fy, w: fw, h: h + 2 * pad },
+            anchors,
+        });
+        x += fw;
+    }
+
+    let path = Path::new(&params.path);
+    match mode {
+        SpriteMode::Sheet => {
+            let height = frames.last().map_or(0, |f| f.frame.y + f.frame.h);
+            let mut canvas = canvas(sheet_width, height, params);
+            for (f, (_, _, poses)) in frames.iter().zip(characters.iter()) {
+                draw(&mut canvas, f, poses, params);
+            }
+            save(canvas, sheet_width, height, path, params)?;
+            let image = path.file_name().map(|n| n.to_string_lossy().into());
+            write_atlas(&Atlas { image, frames }, &path.with_extension("json"))
+        },
+        SpriteMode::Files => {
+            fs::create_dir_all(path)?;
+            for (f, (_, _, poses)) in frames.iter_mut().zip(characters.iter()) {
+                let (fw, fh) = (f.frame.w, f.frame.h);
+                let mut canvas = canvas(fw, fh, params);
+                draw(&mut canvas, f, poses, params);
+                // named after the code point, characters make poor file names
+                let name = format!("{}_U+{:04X}.png", f.mode.replace(
+                    |c: char| !c.is_ascii_alphanumeric(), "-"),
+                    f.char.chars().next().map_or(0, |c| c as u32));
+                save(canvas, fw, fh, &path.join(&name), params)?;
+                f.file = Some(name);
+            }
+            write_atlas(&Atlas { image: None, frames }, &path.join("atlas.json"))
+        },
+    }
+}
+
+fn canvas(width: i32, height: i32, params: &SmphrParams)
+    -> RasterCanvas<Buffer> {
+    let buffer = Buffer::new(width as usize, height as usize,
+                             params.palette.background);
+    RasterCanvas::new(buffer, params.antialias)
+}
+
+/* Draws the figures of a frame on its anchors. */
+fn draw(canvas: &mut RasterCanvas<Buffer>, frame: &Frame, poses: &[Pose],
+        params: &SmphrParams) {
+    let figures = Stickman::from_sign(&Sign::Strokes(poses.to_vec()));
+    for (s, a) in figures.iter().zip(frame.anchors.iter()) {
+        let pos = Point::from(frame.frame.x + a.x, frame.frame.y + a.y);
+        s.set_pos(pos).draw(canvas, params);
+    }
+}
+
+fn save(canvas: RasterCanvas<Buffer>, width: i32, height: i32, path: &Path,
+        params: &SmphrParams) -> Result<(), ImageError> {
+    let buffer = canvas.into_pixels();
+    sized_image(buffer.pixels(), width as u32, height as u32, params)
+        .save(path)
+}
+
+fn write_atlas(atlas: &Atlas, path: &Path) -> Result<(), ImageError> {
+    let file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(file, atlas)
+        .map_err(|e| ImageError::IoError(e.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lays_out_the_sheet_of_latin() {
+        let mut params = SmphrParams::default();
+        params.path = std::env::temp_dir()
+            .join(format!("smphr-sheet-{}.png", std::process::id()))
+            .to_string_lossy().into_owned();
+        export(&params, SpriteMode::Sheet).ok().unwrap();
+
+        let path = Path::new(&params.path);
+        let sheet = image::open(path).unwrap().to_rgba8();
+        let json = fs::read_to_string(path.with_extension("json")).unwrap();
+        let atlas: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let frames = atlas["frames"].as_array().unwrap();
+        let int = |v: &serde_json::Value, k| v[k].as_i64().unwrap() as i32;
+        let rects: Vec<_> = frames.iter().map(|f| &f["frame"])
+            .map(|r| (int(r, "x"), int(r, "y"), int(r, "w"), int(r, "h")))
+            .collect();
+
+        // every character once, each on its own part of the sheet
+        let chars: Vec<_> = frames.iter()
+            .map(|f| f["char"].as_str().unwrap().to_string()).collect();
+        let expected: Vec<_> = params.alphabet.characters().iter()
+            .map(|(_, c, _)| c.to_string()).collect();
+        assert_eq!(chars, expected);
+        for (i, &(x, y, w, h)) in rects.iter().enumerate() {
+            assert!(x >= 0 && y >= 0 && x + w <= sheet.width() as i32
+                    && y + h <= sheet.height() as i32);
+            for &(x2, y2, w2, h2) in rects[i + 1..].iter() {
+                assert!(x + w <= x2 || x2 + w2 <= x
+                        || y + h <= y2 || y2 + h2 <= y);
+            }
+        }
+
+        // the body crosses the anchor, and the feet are on either side
+        let (body, s) = (params.palette.body, params.scale);
+        for (f, (x, y, _, _)) in frames.iter().zip(rects.iter()) {
+            for a in f["anchors"].as_array().unwrap() {
+                let (ax, ay) = (x + int(a, "x"), y + int(a, "y"));
+                assert_eq!(*sheet.get_pixel(ax as u32, ay as u32), body);
+                for dy in (34.0 * s) as i32..(40.0 * s) as i32 {
+                    for dx in 0..(15.0 * s) as i32 {
+                        let at = |x: i32| *sheet.get_pixel(x as u32,
+                                                           (ay + dy) as u32);
+                        assert_eq!(at(ax - dx), at(ax + dx), "{}", f["char"]);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use super::stroke::Contour;
+use image::Rgba;
+
+/// Surface the figures are drawn on. Coordinates are in pixels of the
+/// output, (x, y) being the centre of pixel (x, y); whatever lies out of
+/// the surface is clipped by the backend.
+pub trait Canvas {
+    /// Line going from one point to the other, both ends included, one
+    /// pixel wide or wider.
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64,
+            color: Rgba<u8>);
+
+    /// Ring of the given thickness, from radius r outwards.
+    fn circle(&mut self, centre: (f64, f64), r: f64, thickness: f64,
+              color: Rgba<u8>);
+
+    /// Polygon filled edges included, so that a flat one still shows.
+    fn polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>);
+
+    /// Contours filled together with the non-zero winding rule, such as
+    /// the outline of a stroke.
+    fn fill(&mut self, contours: &[Contour], color: Rgba<u8>);
+
+    /// Calls draw with the origin moved to (x, y), draw going no further
+    /// than reach from it either way. Figures with the same key must look
+    /// the same, so that backends can draw one once and copy it, which is
+    /// why the origin only moves by whole pixels.
+    fn figure(&mut self, key: u32, x: i32, y: i32, reach: i32,
+              draw: &dyn Fn(&mut dyn Canvas));
+}
@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+
+static SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Text chunk of a PNG file giving the value of the keyword: tEXt when the
+/// value is plain ASCII, iTXt, in UTF-8, otherwise.
+pub fn text_chunk(keyword: &str, value: &str) -> ([u8; 4], Vec<u8>) {
+    let mut data = keyword.as_bytes().to_vec();
+    data.push(0);
+    if value.is_ascii() {
+        data.extend_from_slice(value.as_bytes());
+        return (*b"tEXt", data);
+    }
+    // not compressed, with neither language nor translated keyword
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data.extend_from_slice(value.as_bytes());
+    (*b"iTXt", data)
+}
+
+/// Keywords and values of the text chunks of a PNG file, in order. Returns
+/// None when the file is not a PNG.
+pub fn read_text(path: &str) -> io::Result<Option<Vec<(String, String)>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut signature = [0; 8];
+    if file.read_exact(&mut signature).is_err() || signature != SIGNATURE {
+        return Ok(None);
+    }
+
+    let mut text = Vec::new();
+    loop {
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2],
+                                      header[3]]) as u64;
+        let kind = [header[4], header[5], header[6], header[7]];
+        if kind == *b"IEND" { break; }
+        if kind != *b"tEXt" && kind != *b"iTXt" {
+            // skipped along with its CRC
+            io::copy(&mut (&mut file).take(len + 4), &mut io::sink())?;
+            continue;
+        }
+        let mut data = vec![0; len as usize];
+        file.read_exact(&mut data)?;
+        file.read_exact(&mut [0; 4])?;
+        if let Some(entry) = parse_text(&kind, &data) {
+            text.push(entry);
+        }
+    }
+    Ok(Some(text))
+}
+
+/* Keyword and value of a text chunk, tEXt being in Latin-1. Compressed
+   values are not read. */
+fn parse_text(kind: &[u8; 4], data: &[u8]) -> Option<(String, String)> {
+    let latin1 = |b: &[u8]| b.iter().map(|&c| c as char).collect::<String>();
+    let end = data.iter().position(|&b| b == 0)?;
+    let keyword = latin1(&data[..end]);
+    let rest = &data[end + 1..];
+    if kind == b"tEXt" {
+        return Some((keyword, latin1(rest)));
+    }
+
+    // compression flag and method, then two null-terminated fields
+    let (&compressed, rest) = rest.split_first()?;
+    let mut rest = rest.get(1..)?;
+    for _ in 0..2 {
+        let end = rest.iter().position(|&b| b == 0)?;
+        rest = &rest[end + 1..];
+    }
+    let value = if compressed != 0 {
+        "(compressed)".to_string()
+    } else {
+        String::from_utf8_lossy(rest).into_owned()
+    };
+    Some((keyword, value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_back_text() {
+        for value in ["Hello", "Привет\nмир"] {
+            let (kind, data) = text_chunk("Description", value);
+            assert_eq!(parse_text(&kind, &data),
+                       Some(("Description".to_string(), value.to_string())));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use super::canvas::Canvas;
+use image::Rgba;
+
+/* Capitals and digits drawn as lines on a grid 4 units wide and 6 high, y
+   going down. */
+type Glyph = &'static [&'static [(i8, i8)]];
+
+static LETTERS: [Glyph; 26] = [
+    &[&[(0, 6), (0, 2), (2, 0), (4, 2), (4, 6)], &[(0, 4), (4, 4)]],
+    &[&[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+      &[(0, 0), (3, 0), (4, 1), (4, 2), (3, 3)]],
+    &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5)]],
+    &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+    &[&[(4, 0), (0, 0), (0, 6), (4, 6)], &[(0, 3), (3, 3)]],
+    &[&[(4, 0), (0, 0), (0, 6)], &[(0, 3), (3, 3)]],
+    &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5),
+        (4, 3), (2, 3)]],
+    &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+    &[&[(1, 0), (3, 0)], &[(2, 0), (2, 6)], &[(1, 6), (3, 6)]],
+    &[&[(4, 0), (4, 5), (3, 6), (1, 6), (0, 5)]],
+    &[&[(0, 0), (0, 6)], &[(4, 0), (0, 4)], &[(1, 3), (4, 6)]],
+    &[&[(0, 0), (0, 6), (4, 6)]],
+    &[&[(0, 6), (0, 0), (2, 3), (4, 0), (4, 6)]],
+    &[&[(0, 6), (0, 0), (4, 6), (4, 0)]],
+    &[&[(1, 0), (3, 0), (4, 1), (4, 5), (3, 6), (1, 6), (0, 5), (0, 1),
+        (1, 0)]],
+    &[&[(0, 6), (0, 0), (3, 0), (4, 1), (4, 2), (3, 3), (0, 3)]],
+    &[&[(1, 0), (3, 0), (4, 1), (4, 5), (3, 6), (1, 6), (0, 5), (0, 1),
+        (1, 0)], &[(2, 4), (4, 6)]],
+    &[&[(0, 6), (0, 0), (3, 0), (4, 1), (4, 2), (3, 3), (0, 3)],
+      &[(2, 3), (4, 6)]],
+    &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 2), (1, 3), (3, 3), (4, 4),
+        (4, 5), (3, 6), (1, 6), (0, 5)]],
+    &[&[(0, 0), (4, 0)], &[(2, 0), (2, 6)]],
+    &[&[(0, 0), (0, 5), (1, 6), (3, 6), (4, 5), (4, 0)]],
+    &[&[(0, 0), (2, 6), (4, 0)]],
+    &[&[(0, 0), (1, 6), (2, 3), (3, 6), (4, 0)]],
+    &[&[(0, 0), (4, 6)], &[(4, 0), (0, 6)]],
+    &[&[(0, 0), (2, 3), (4, 0)], &[(2, 3), (2, 6)]],
+    &[&[(0, 0), (4, 0), (0, 6), (4, 6)]],
+];
+
+static DIGITS: [Glyph; 10] = [
+    &[&[(1, 0), (3, 0), (4, 1), (4, 5), (3, 6), (1, 6), (0, 5), (0, 1),
+        (1, 0)], &[(4, 1), (0, 5)]],
+    &[&[(1, 1), (2, 0), (2, 6)], &[(1, 6), (3, 6)]],
+    &[&[(0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (0, 6), (4, 6)]],
+    &[&[(0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (3, 3), (4, 4), (4, 5),
+        (3, 6), (1, 6), (0, 5)], &[(1, 3), (3, 3)]],
+    &[&[(3, 6), (3, 0), (0, 4), (4, 4)]],
+    &[&[(4, 0), (0, 0), (0, 3), (3, 3), (4, 4), (4, 5), (3, 6), (0, 6)]],
+    &[&[(3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5), (4, 4),
+        (3, 3), (0, 3)]],
+    &[&[(0, 0), (4, 0), (1, 6)]],
+    &[&[(1, 3), (0, 2), (0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (3, 3),
+        (1, 3), (0, 4), (0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (3, 3)]],
+    &[&[(4, 3), (1, 3), (0, 2), (0, 1), (1, 0), (3, 0), (4, 1), (4, 5),
+        (3, 6), (1, 6)]],
+];
+
+/* Units from a character to the next one. */
+static ADVANCE: f64 = 6.0;
+
+/// Width of a label written size high.
+pub fn label_width(text: &str, size: f64) -> f64 {
+    let n = text.chars().count() as f64;
+    ((n * ADVANCE - 2.0) * size / 6.0).max(0.0)
+}
+
+/// Writes a label made of capitals and digits, size high and centred on
+/// (x, y), with lines of the given width. Other characters are left blank.
+pub fn draw_label(canvas: &mut dyn Canvas, text: &str, (x, y): (f64, f64),
+                  size: f64, width: f64, color: Rgba<u8>) {
+    let unit = size / 6.0;
+    let (x0, y0) = (x - label_width(text, size) / 2.0, y - size / 2.0);
+    for (i, c) in text.chars().enumerate() {
+        let glyph = match c {
+            'A'..='Z' => LETTERS[c as usize - 'A' as usize],
+            '0'..='9' => DIGITS[c as usize - '0' as usize],
+            _ => continue,
+        };
+        let left = x0 + i as f64 * ADVANCE * unit;
+        let at = |(gx, gy): (i8, i8)| (left + gx as f64 * unit,
+                                       y0 + gy as f64 * unit);
+        for line in glyph.iter() {
+            for pair in line.windows(2) {
+                canvas.line(at(pair[0]), at(pair[1]), width, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,581 @@
+use super::SmphrParams;
+use super::alphabet::Sign;
+use super::canvas::Canvas;
+use super::glyphs::{draw_label, label_width};
+use super::stickman::{mark_room, stick_height, stick_width, Point, Stickman};
+
+/// Direction of the lines of figures.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Writing {
+    /// Rows, going down the image.
+    Horizontal,
+    /// Columns from top to bottom, going across the image.
+    Vertical,
+}
+
+/// Where the figures of a row stand when there is room left on it. In
+/// columns, left stands for the top; from right to left, for the right.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Centre,
+    Right,
+    /// Spread over the whole width, but for the last row of a paragraph.
+    Justify,
+}
+
+/// Where the block of rows stands when there is room left under it. For
+/// columns, top stands for the side the first one is on.
+#[derive(Clone, Copy, PartialEq)]
+pub enum VAlign {
+    Top,
+    Centre,
+    Bottom,
+}
+
+/// Fixed grid of cells, one per character, as on a worksheet.
+pub struct Grid {
+    /// Number of columns, as many as fit when None or when fewer fit.
+    pub columns: Option<usize>,
+    /// Whether the cells are outlined.
+    pub borders: bool,
+    /// Whether the columns are named with letters and the rows numbered.
+    pub labels: bool,
+    /// Every how many characters a cell is left empty, to be filled in.
+    pub blank: Option<usize>,
+}
+
+/// How the stickmen are placed on the image. Lengths are in pixels.
+pub struct Layout {
+    /// Space kept free around the stickmen.
+    pub margin: i32,
+    /// Space between two figures of a row.
+    pub gap: i32,
+    /// Space between two rows.
+    pub row_gap: i32,
+    /// Width of a space between words, that of a figure when None.
+    pub word: Option<i32>,
+    pub align: Align,
+    pub valign: VAlign,
+    pub writing: Writing,
+    /// Whether rows start on the right, and columns go from right to left.
+    pub rtl: bool,
+    /// Grid the characters are placed in instead of flowing, from the left.
+    pub grid: Option<Grid>,
+}
+
+impl Layout {
+    pub fn default() -> Layout {
+        Layout {
+            margin: 0,
+            gap: 0,
+            row_gap: 0,
+            word: None,
+            align: Align::Left,
+            valign: VAlign::Top,
+            writing: Writing::Horizontal,
+            rtl: false,
+            grid: None,
+        }
+    }
+}
+
+/// Stickmen signalling a sign, placed on the image.
+pub struct Placed {
+    /// Index of the sign among those laid out.
+    pub sign: usize,
+    /// Row the first stickman is on, or column, from the first one.
+    pub row: usize,
+    pub figures: Vec<Stickman>,
+}
+
+/* Figure of a row: its group, its place in the group and its width. */
+type Cell = (usize, usize, i32);
+
+struct Row {
+    cells: Vec<Cell>,
+    width: i32,
+    /* whether the row ends a paragraph, which is never justified */
+    last: bool,
+    /* whether it holds a figure of a group, marked beside the row */
+    marked: bool,
+}
+
+impl Row {
+    fn new() -> Row {
+        Row { cells: Vec::new(), width: 0, last: false, marked: false }
+    }
+}
+
+/// Stickmen signalling each sign, placed on the image, with the number of
+/// signs that fit, the text being cut after them.
+pub fn layout(signs: &[Sign], params: &SmphrParams)
+    -> (Vec<Vec<Stickman>>, usize) {
+    let (placed, n) = place(signs, params);
+    (placed.into_iter().map(|p| p.figures).collect(), n)
+}
+
+/// Signs placed on the image, but for those drawing nothing, with the number
+/// of signs that fit. The poses of a sign are kept on the same row when it
+/// fits on one, and new lines start new rows. Columns are laid out as rows
+/// along the other axis.
+pub fn place(signs: &[Sign], params: &SmphrParams) -> (Vec<Placed>, usize) {
+    let l = &params.layout;
+    if let Some(grid) = &l.grid {
+        return grid_layout(signs, params, grid);
+    }
+    let vertical = l.writing == Writing::Vertical;
+    // along the rows, then across them
+    let (w, h) = (stick_width(params), stick_height(params));
+    let (w, h) = if vertical { (h, w) } else { (w, h) };
+    let inner = (params.width as i32 - 2 * l.margin,
+                 params.height as i32 - 2 * l.margin);
+    let inner = if vertical { (inner.1, inner.0) } else { inner };
+    // rows holding a group keep room for its mark after them
+    let mark = mark_room(params);
+    let size = |row: &Row| h + if row.marked { mark } else { 0 };
+    let extent = |rows: &[Row]| {
+        rows.iter().map(size).sum::<i32>() + l.row_gap * (rows.len() as i32 - 1)
+    };
+    let fits = |row: &Row, width: i32| {
+        row.cells.is_empty() || row.width + l.gap + width <= inner.0
+    };
+    let push = |row: &mut Row, cell: Cell| {
+        if !row.cells.is_empty() { row.width += l.gap; }
+        row.width += cell.2;
+        row.cells.push(cell);
+    };
+
+    let mut groups: Vec<Vec<Stickman>> = Vec::new();
+    // sign of each group and row of its first figure
+    let mut origins = Vec::new();
+    let mut rows = vec![Row::new()];
+    let mut count = signs.len();
+    for (i, sign) in signs.iter().enumerate() {
+        if let Sign::NewLine = sign {
+            rows.last_mut().unwrap().last = true;
+            rows.push(Row::new());
+            continue;
+        }
+        let figures = Stickman::from_sign(sign);
+        if figures.is_empty() { continue; }
+        let width = match sign {
+            Sign::Space => l.word.unwrap_or(w),
+            _ => w,
+        };
+
+        // taken back when the sign does not fit
+        let n = rows.len();
+        let last = &rows[n - 1];
+        let (len, filled, marked) = (last.cells.len(), last.width, last.marked);
+        let whole = width * figures.len() as i32
+                    + l.gap * (figures.len() as i32 - 1);
+        if !fits(&rows[n - 1], whole) && whole <= inner.0 {
+            rows.push(Row::new());
+        }
+        let mut first = 0;
+        for k in 0..figures.len() {
+            if !fits(&rows[rows.len() - 1], width) {
+                rows.push(Row::new());
+            }
+            if k == 0 { first = rows.len() - 1; }
+            let row = rows.last_mut().unwrap();
+            push(row, (groups.len(), k, width));
+            row.marked |= figures.len() > 1;
+        }
+        if extent(&rows) > inner.1 {
+            rows.truncate(n);
+            let last = &mut rows[n - 1];
+            last.cells.truncate(len);
+            last.width = filled;
+            last.marked = marked;
+            count = i;
+            break;
+        }
+        groups.push(figures);
+        origins.push((i, first));
+    }
+
+    // rows left empty at the end take no room
+    while rows.len() > 1 && rows[rows.len() - 1].cells.is_empty() {
+        rows.pop();
+    }
+    rows.last_mut().unwrap().last = true;
+    let block = extent(&rows);
+    let top = l.margin + match l.valign {
+        VAlign::Top => 0,
+        VAlign::Centre => (inner.1 - block) / 2,
+        VAlign::Bottom => inner.1 - block,
+    };
+
+    let mut placed: Vec<Vec<Stickman>> = groups.iter()
+        .map(|g| Vec::with_capacity(g.len())).collect();
+    let mut y = top;
+    for row in rows.iter() {
+        let room = inner.0 - row.width;
+        let (start, spread) = match l.align {
+            Align::Left => (0, 0),
+            Align::Centre => (room / 2, 0),
+            Align::Right => (room, 0),
+            Align::Justify if row.last => (0, 0),
+            Align::Justify => (0, room),
+        };
+        let gaps = (row.cells.len() as i32 - 1).max(1);
+        let mut x = l.margin + start;
+        for (k, &(g, f, width)) in row.cells.iter().enumerate() {
+            // cells are turned over from their first corner
+            let far = 2 * l.margin + if vertical { inner.1 } else { inner.0 };
+            let pos = match (vertical, l.rtl) {
+                (false, false) => Point::from(x, y),
+                (false, true) => Point::from(far - x - width, y),
+                (true, false) => Point::from(y, x),
+                (true, true) => Point::from(far - y - h, x),
+            };
+            let (dx, dy) = if vertical { (h, width) } else { (width, h) };
+            let pos = Point::from(pos.x + dx / 2, pos.y + dy / 2);
+            placed[g].push(groups[g][f].set_pos(pos));
+            // the room left is shared out evenly between the gaps
+            let k = k as i32;
+            x += width + l.gap + spread * (k + 1) / gaps - spread * k / gaps;
+        }
+        y += size(row) + l.row_gap;
+    }
+
+    let placed = placed.into_iter().zip(origins)
+        .map(|(figures, (sign, row))| Placed { sign, row, figures })
+        .collect();
+    (placed, count)
+}
+
+/* Cells of a grid: the corner of the first one, their size, how many there
+   are across and down, and the size of the labels with the room they take
+   on the left. */
+struct Cells {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    /* room for the group mark at the bottom of each cell */
+    mark: i32,
+    columns: usize,
+    rows: usize,
+    label: f64,
+    left: i32,
+}
+
+impl Cells {
+    /* Cells as wide as the longest character of the alphabet, as many as
+       fit or as asked for if fewer, leaving room for the labels. */
+    fn new(grid: &Grid, params: &SmphrParams) -> Cells {
+        let l = &params.layout;
+        let poses = params.alphabet.max_strokes().max(1) as i32;
+        let (w, h) = (stick_width(params), stick_height(params));
+        // room for the mark under characters of several poses
+        let mark = if poses > 1 { mark_room(params) } else { 0 };
+        let (width, height) = (poses * w + l.gap, h + mark + l.row_gap);
+        let inner = (params.width as i32 - 2 * l.margin,
+                     params.height as i32 - 2 * l.margin);
+
+        let label = if grid.labels { (h / 6) as f64 } else { 0.0 };
+        // a line of labels above the cells, the row numbers on their left
+        let top = (2.0 * label).ceil() as i32;
+        let rows = ((inner.1 - top) / height).max(0) as usize;
+        let left = if grid.labels {
+            (label_width(&rows.to_string(), label) + 2.0 * label).ceil() as i32
+        } else { 0 };
+        let fit = ((inner.0 - left) / width).max(1) as usize;
+        Cells {
+            x: l.margin + left,
+            y: l.margin + top,
+            width,
+            height,
+            mark,
+            columns: grid.columns.map_or(fit, |n| n.min(fit)).max(1),
+            rows,
+            label,
+            left,
+        }
+    }
+}
+
+/* Stickmen placed one character per cell, the poses of a character side by
+   side in the middle of it, with the number of signs that fit. */
+fn grid_layout(signs: &[Sign], params: &SmphrParams, grid: &Grid)
+    -> (Vec<Placed>, usize) {
+
+    let cells = Cells::new(grid, params);
+    let (w, h) = (stick_width(params), stick_height(params));
+    let mut groups = Vec::new();
+    let (mut col, mut row) = (0, 0);
+    // a new line right after a full row does not leave one empty
+    let mut wrapped = false;
+    let mut written = 0;
+    for (i, sign) in signs.iter().enumerate() {
+        if let Sign::NewLine = sign {
+            if !wrapped { row += 1; }
+            col = 0;
+            wrapped = false;
+            continue;
+        }
+        if row >= cells.rows { return (groups, i); }
+
+        let figures = Stickman::from_sign(sign);
+        let blank = match sign {
+            Sign::Pose(_) | Sign::Strokes(_) => {
+                written += 1;
+                grid.blank.is_some_and(|n| written % n == 0)
+            },
+            _ => false,
+        };
+        if !blank && !figures.is_empty() {
+            let n = figures.len() as i32;
+            let x = cells.x + col as i32 * cells.width
+                    + (cells.width - n * w) / 2 + w / 2;
+            let y = cells.y + row as i32 * cells.height
+                    + (cells.height - cells.mark - h) / 2 + h / 2;
+            let figures = figures.iter().enumerate()
+                .map(|(k, s)| s.set_pos(Point::from(x + k as i32 * w, y)))
+                .collect();
+            groups.push(Placed { sign: i, row, figures });
+        }
+
+        col += 1;
+        wrapped = col == cells.columns;
+        if wrapped {
+            col = 0;
+            row += 1;
+        }
+    }
+
+    (groups, signs.len())
+}
+
+/* Letters naming the columns of a grid: A to Z, then AA, AB and so on. */
+fn column_name(mut k: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (k % 26) as u8);
+        if k < 26 { break; }
+        k = k / 26 - 1;
+    }
+    name.iter().rev().map(|&b| b as char).collect()
+}
+
+/// Draws the borders and labels of the grid, if any, near the rows from top
+/// to bottom, excluded.
+pub fn draw_grid(params: &SmphrParams, canvas: &mut dyn Canvas, top: i32,
+                 bottom: i32) {
+    let grid = match &params.layout.grid {
+        Some(g) => g,
+        None => return,
+    };
+    let c = Cells::new(grid, params);
+    let (s, color) = (params.scale, params.palette.body);
+    // whether something drawn around y, reaching d from it, shows
+    let shows = |y: f64, d: f64| y + d >= top as f64 && y - d < bottom as f64;
+    let (x1, y1) = (c.x + c.columns as i32 * c.width,
+                    c.y + c.rows as i32 * c.height);
+
+    if grid.borders {
+        for r in 0..=c.rows {
+            let y = (c.y + r as i32 * c.height) as f64;
+            if !shows(y, s + 1.0) { continue; }
+            canvas.line((c.x as f64, y), (x1 as f64, y), s, color);
+        }
+        for k in 0..=c.columns {
+            let x = (c.x + k as i32 * c.width) as f64;
+            canvas.line((x, c.y as f64), (x, y1 as f64), s, color);
+        }
+    }
+
+    if grid.labels {
+        let y = c.y as f64 - c.label;
+        if shows(y, c.label + s) {
+            for k in 0..c.columns {
+                let x = (c.x + k as i32 * c.width) as f64 + c.width as f64 / 2.0;
+                draw_label(canvas, &column_name(k), (x, y), c.label, s, color);
+            }
+        }
+        let x = (c.x - c.left / 2) as f64;
+        for r in 0..c.rows {
+            let y = (c.y + r as i32 * c.height) as f64 + c.height as f64 / 2.0;
+            if !shows(y, c.label + s) { continue; }
+            draw_label(canvas, &(r + 1).to_string(), (x, y), c.label, s,
+                       color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::alphabet::Pose;
+
+    fn letters(n: usize) -> Vec<Sign> {
+        vec![Sign::Pose(Pose::from(1, 0)); n]
+    }
+
+    /* Centre of the first figure of each placed sign. */
+    fn centres(signs: &[Sign], params: &SmphrParams) -> Vec<(i32, i32)> {
+        place(signs, params).0.iter()
+            .map(|p| (p.figures[0].get_pos().x, p.figures[0].get_pos().y))
+            .collect()
+    }
+
+    #[test]
+    fn aligns_rows() {
+        let mut params = SmphrParams::default();
+        let signs = letters(2);
+        for (align, x) in [(Align::Left, 35), (Align::Centre, 264),
+                           (Align::Right, 493)] {
+            params.layout.align = align;
+            assert_eq!(centres(&signs, &params), [(x, 40), (x + 71, 40)]);
+        }
+    }
+
+    #[test]
+    fn justifies_all_rows_but_the_last() {
+        let mut params = SmphrParams::default();
+        params.layout.align = Align::Justify;
+        // eight figures of 71 pixels on the first row, 32 pixels to spare
+        let xs: Vec<i32> = centres(&letters(9), &params).iter()
+            .map(|c| c.0).collect();
+        let spread: Vec<i32> = (0..8).map(|k| 35 + 71 * k + 32 * k / 7)
+            .collect();
+        assert_eq!(xs[..8], spread[..]);
+        assert_eq!(xs[7], 600 - 71 + 35);
+        assert_eq!(xs[8], 35);
+    }
+
+    #[test]
+    fn places_the_block_of_rows() {
+        let mut params = SmphrParams::default();
+        for (valign, y) in [(VAlign::Top, 40), (VAlign::Centre, 199),
+                            (VAlign::Bottom, 359)] {
+            params.layout.valign = valign;
+            assert_eq!(centres(&letters(1), &params), [(35, y)]);
+        }
+    }
+
+    #[test]
+    fn spaces_words() {
+        let mut params = SmphrParams::default();
+        let signs = [Sign::Pose(Pose::from(1, 0)), Sign::Space,
+                     Sign::Pose(Pose::from(1, 0))];
+        assert_eq!(centres(&signs, &params).last(), Some(&(177, 40)));
+        params.layout.word = Some(20);
+        assert_eq!(centres(&signs, &params).last(), Some(&(126, 40)));
+    }
+
+    #[test]
+    fn lays_out_columns() {
+        let mut params = SmphrParams::default();
+        params.layout.writing = Writing::Vertical;
+        // four figures of 81 pixels down each column
+        let signs = letters(5);
+        let column = [(35, 40), (35, 121), (35, 202), (35, 283)];
+        assert_eq!(centres(&signs, &params)[..4], column);
+        assert_eq!(centres(&signs, &params)[4], (106, 40));
+        params.layout.rtl = true;
+        assert_eq!(centres(&signs, &params)[0], (564, 40));
+        assert_eq!(centres(&signs, &params)[4], (493, 40));
+    }
+
+    #[test]
+    fn lays_out_rows_from_the_right() {
+        let mut params = SmphrParams::default();
+        params.layout.rtl = true;
+        assert_eq!(centres(&letters(9), &params)[..2], [(564, 40), (493, 40)]);
+        assert_eq!(centres(&letters(9), &params)[8], (564, 121));
+    }
+
+    #[test]
+    fn leaves_room_for_the_mark_of_groups() {
+        let mut params = SmphrParams::default();
+        let room = mark_room(&params);
+        let group = Sign::Strokes(vec![Pose::from(1, 0), Pose::from(2, 0)]);
+        let mut signs = vec![group];
+        signs.extend(letters(7));
+        // after the row, or the column, holding the group only
+        assert_eq!(centres(&signs, &params)[7], (35, 81 + room + 40));
+        params.layout.writing = Writing::Vertical;
+        assert_eq!(centres(&signs, &params)[3], (71 + room + 35, 40));
+        assert_eq!(centres(&signs, &params)[7], (2 * 71 + room + 35, 40));
+        params.layout.rtl = true;
+        assert_eq!(centres(&signs, &params)[3], (600 - 2 * 71 - room + 35, 40));
+    }
+
+    fn grid(columns: Option<usize>, blank: Option<usize>) -> Option<Grid> {
+        Some(Grid { columns, borders: false, labels: false, blank })
+    }
+
+    #[test]
+    fn keeps_grid_columns_on_the_image() {
+        let mut params = SmphrParams::default();
+        params.layout.grid = grid(Some(40), None);
+        // no more than the eight cells of 71 pixels that fit
+        let placed = place(&letters(9), &params).0;
+        assert_eq!(placed[8].row, 1);
+        assert_eq!(centres(&letters(9), &params)[7..], [(532, 40), (35, 121)]);
+    }
+
+    #[test]
+    fn wraps_grid_rows_once() {
+        let mut params = SmphrParams::default();
+        params.layout.grid = grid(Some(3), None);
+        // a new line after a full row starts no other one
+        let mut signs = letters(3);
+        signs.push(Sign::NewLine);
+        signs.extend(letters(1));
+        signs.push(Sign::NewLine);
+        signs.extend(letters(1));
+        let rows: Vec<usize> = place(&signs, &params).0.iter()
+            .map(|p| p.row).collect();
+        assert_eq!(rows, [0, 0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn leaves_every_nth_character_blank() {
+        let mut params = SmphrParams::default();
+        params.layout.grid = grid(None, Some(3));
+        // spaces are not counted, and blank cells are kept
+        let mut signs = letters(5);
+        signs.insert(1, Sign::Space);
+        let (placed, n) = place(&signs, &params);
+        let indices: Vec<usize> = placed.iter().map(|p| p.sign).collect();
+        assert_eq!(indices, [0, 1, 2, 4, 5]);
+        assert_eq!(n, 6);
+        assert_eq!(centres(&signs, &params)[4], (5 * 71 + 35, 40));
+    }
+
+    #[test]
+    fn names_grid_columns() {
+        let names: Vec<String> = [0, 25, 26, 27, 51, 701, 702].iter()
+            .map(|&k| column_name(k)).collect();
+        assert_eq!(names, ["A", "Z", "AA", "AB", "AZ", "ZZ", "AAA"]);
+    }
+
+    #[test]
+    fn takes_back_a_sign_that_does_not_fit() {
+        let mut params = SmphrParams::default();
+        params.height = stick_height(&params) as u32;
+        params.layout.align = Align::Right;
+        // the mark of the group would not fit under the only row
+        let group = Sign::Strokes(vec![Pose::from(1, 0), Pose::from(2, 0)]);
+        let signs = [Sign::Pose(Pose::from(1, 0)), group];
+        let (placed, n) = place(&signs, &params);
+        assert_eq!(n, 1);
+        assert_eq!(placed.len(), 1);
+        assert_eq!(centres(&signs, &params), [(564, 40)]);
+    }
+
+    #[test]
+    fn cuts_the_text_after_the_last_whole_row() {
+        // 600 by 400 pixels hold four rows of eight figures
+        let params = SmphrParams::default();
+        let (placed, n) = place(&letters(40), &params);
+        assert_eq!(n, 32);
+        assert_eq!(placed.len(), 32);
+        assert_eq!(placed.iter().map(|p| p.row).max(), Some(3));
+    }
+}
@@ -0,0 +1,341 @@
+use super::{paginate, Format, SmphrParams, INCH_PT};
+use super::alphabet::Sign;
+use super::layout::{place, Writing};
+use super::raster::{Pixels, RasterCanvas};
+use super::stickman::{stick_height, stick_width, Point, Stickman,
+                      StickmanError};
+use image::{ImageError, Rgba};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Serialize)]
+struct Centre {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Bounds {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+}
+
+#[derive(Serialize)]
+struct Entry {
+    /// Place of the character in the text, from 0.
+    index: usize,
+    char: String,
+    /// Mode a mode switch leads to, listed before the character needing it,
+    /// with its index and no character of its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    switch: Option<String>,
+    poses: Vec<[u8; 2]>,
+    /// Centre of its figure, or halfway between the first and the last.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cg: Option<Centre>,
+    /// Box of the pixels drawn for it on the image or the page, flags
+    /// included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bbox: Option<Bounds>,
+    /// Row, or column, of its first figure, from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    row: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page: Option<usize>,
+    /// Why nothing is drawn for the character, if so.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Metadata {
+    characters: Vec<Entry>,
+}
+
+/// Writes where each character of the text ended up, in pixels from the
+/// corner of the image or of the page, as CSV when the path ends in .csv and
+/// as JSON otherwise. Characters drawing nothing are listed with the reason.
+pub fn export(path: &str, text: &str,
+              encoded: &[(usize, Result<Sign, StickmanError>)],
+              params: &SmphrParams) -> Result<(), ImageError> {
+
+    let entries = locate(text, encoded, params);
+    let mut file = BufWriter::new(File::create(path)?);
+    let csv = Path::new(path).extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    if csv {
+        write_csv(&mut file, &entries)?;
+    } else {
+        serde_json::to_writer_pretty(&mut file,
+                                     &Metadata { characters: entries })
+            .map_err(|e| ImageError::IoError(e.into()))?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+/* Entry of each character of the text, and of each mode switch. */
+fn locate(text: &str, encoded: &[(usize, Result<Sign, StickmanError>)],
+          params: &SmphrParams) -> Vec<Entry> {
+
+    let entry = |index, char: String| Entry {
+        index,
+        char,
+        switch: None,
+        poses: Vec::new(),
+        cg: None,
+        bbox: None,
+        row: None,
+        page: None,
+        skipped: None,
+    };
+    let chars: Vec<char> = text.chars().collect();
+
+    // signs are laid out as for the image, remembering their entry; a mode
+    // switch comes with the index of the character after it, and has its own
+    let mut entries = Vec::with_capacity(encoded.len());
+    let mut signs = Vec::new();
+    let mut sources = Vec::new();
+    for (k, (i, sign)) in encoded.iter().enumerate() {
+        let switch = encoded.get(k + 1).is_some_and(|(next, _)| next == i);
+        let mut e = entry(*i, if switch { String::new() }
+                              else { chars[*i].to_string() });
+        match sign {
+            Ok(s) => {
+                let poses = match s {
+                    Sign::Pose(p) => vec![*p],
+                    Sign::Strokes(v) => v.clone(),
+                    _ => Vec::new(),
+                };
+                if switch {
+                    e.switch = poses.first().and_then(|p| {
+                        params.alphabet.switched_to(*p).map(String::from)
+                    });
+                }
+                e.poses = poses.iter().map(|p| [p.right, p.left]).collect();
+                signs.push(s.clone());
+                sources.push(entries.len());
+            },
+            Err(e2) => e.skipped = Some(e2.to_string()),
+        }
+        entries.push(e);
+    }
+
+    // PDF pages are laid out within their margins, one after the other
+    let paged = params.format == Format::Pdf && !params.page.poster;
+    let (pages, n) = if paged {
+        paginate(&signs, params)
+    } else {
+        let (placed, n) = place(&signs, params);
+        (vec![(0, placed)], n)
+    };
+    let px = params.dpi / INCH_PT;
+    let (margin, size) = if paged {
+        let page = &params.page;
+        ((page.margin * px).round() as i32,
+         ((page.width * px).round() as i32, (page.height * px).round() as i32))
+    } else {
+        (0, (params.width as i32, params.height as i32))
+    };
+
+    // figures are boxed by the pixels they draw, spaces as laid out
+    let (w, h) = (stick_width(params), stick_height(params));
+    let l = &params.layout;
+    let space = match (l.grid.is_some(), l.writing) {
+        (true, _) => (w, h),
+        (false, Writing::Horizontal) => (l.word.unwrap_or(w), h),
+        (false, Writing::Vertical) => (w, l.word.unwrap_or(h)),
+    };
+    for (p, (start, placed)) in pages.iter().enumerate() {
+        for group in placed.iter() {
+            let e = &mut entries[sources[start + group.sign]];
+            e.row.get_or_insert(group.row + 1);
+            e.page.get_or_insert(p + 1);
+            let at = |s: &Stickman| {
+                let pos = s.get_pos();
+                Point::from(margin + pos.x, margin + pos.y)
+            };
+            let (first, last) = match (group.figures.first(),
+                                       group.figures.last()) {
+                (Some(a), Some(b)) => (at(a), at(b)),
+                _ => continue,
+            };
+            e.cg = Some(Centre { x: (first.x + last.x) / 2,
+                                 y: (first.y + last.y) / 2 });
+            let mut extent = Extent { size, bounds: None };
+            if let Sign::Space = signs[start + group.sign] {
+                let (x, y) = (first.x - space.0 / 2, first.y - space.1 / 2);
+                let (x0, y0) = (x.max(0), y.max(0));
+                let (x1, y1) = ((x + space.0).min(size.0),
+                                (y + space.1).min(size.1));
+                if x0 < x1 && y0 < y1 {
+                    extent.bounds = Some((x0, y0, x1, y1));
+                }
+            } else {
+                let mut canvas = RasterCanvas::new(extent, params.antialias);
+                for s in group.figures.iter() {
+                    s.set_pos(at(s)).draw(&mut canvas, params);
+                }
+                extent = canvas.into_pixels();
+            }
+            e.bbox = extent.bounds.map(|(x0, y0, x1, y1)| {
+                Bounds { x: x0, y: y0, w: x1 - x0, h: y1 - y0 }
+            });
+        }
+    }
+    for &i in sources[n..].iter() {
+        entries[i].skipped = Some("did not fit".to_string());
+    }
+    for e in entries.iter_mut().filter(|e| e.cg.is_none()) {
+        if e.skipped.is_none() {
+            e.skipped = Some(if e.char == "\n" { "new line" }
+                             else { "left blank" }.to_string());
+        }
+    }
+
+    entries
+}
+
+/* Pixels of an image keeping only the box of those written, as (x0, y0,
+   x1, y1) with x1 and y1 excluded. */
+struct Extent {
+    size: (i32, i32),
+    bounds: Option<(i32, i32, i32, i32)>,
+}
+
+impl Extent {
+    fn add(&mut self, x: i32, y: i32) {
+        self.bounds = Some(match self.bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y),
+                                       x1.max(x + 1), y1.max(y + 1)),
+            None => (x, y, x + 1, y + 1),
+        });
+    }
+}
+
+impl Pixels for Extent {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, 0, self.size.0, self.size.1)
+    }
+
+    fn set(&mut self, x: i32, y: i32, _: Rgba<u8>) {
+        self.add(x, y);
+    }
+
+    fn blend(&mut self, x: i32, y: i32, _: Rgba<u8>, coverage: f64) {
+        if coverage > 0.0 {
+            self.add(x, y);
+        }
+    }
+}
+
+/* One line per character, the poses written right/left and separated by
+   spaces, the fields left out empty. */
+fn write_csv(out: &mut dyn Write, entries: &[Entry]) -> std::io::Result<()> {
+    let opt = |v: Option<i32>| v.map_or(String::new(), |v| v.to_string());
+    writeln!(out, "index,char,switch,poses,cg_x,cg_y,x,y,w,h,row,page,\
+skipped")?;
+    for e in entries {
+        let poses: Vec<String> = e.poses.iter()
+            .map(|[r, l]| format!("{}/{}", r, l)).collect();
+        let cg = e.cg.as_ref();
+        let b = e.bbox.as_ref();
+        let fields = [
+            e.index.to_string(),
+            quote(&e.char),
+            quote(e.switch.as_deref().unwrap_or("")),
+            poses.join(" "),
+            opt(cg.map(|c| c.x)),
+            opt(cg.map(|c| c.y)),
+            opt(b.map(|b| b.x)),
+            opt(b.map(|b| b.y)),
+            opt(b.map(|b| b.w)),
+            opt(b.map(|b| b.h)),
+            opt(e.row.map(|r| r as i32)),
+            opt(e.page.map(|p| p as i32)),
+            quote(e.skipped.as_deref().unwrap_or("")),
+        ];
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/* Field quoted when it holds a separator, a quote or a line break. */
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::render;
+
+    #[test]
+    fn boxes_the_pixels_of_each_sign() {
+        let mut params = SmphrParams::default();
+        params.width = 150;
+        let text = "ab1 z";
+        let encoded = params.alphabet.encode(text);
+        let entries = locate(text, &encoded, &params);
+
+        // switches are listed on their own, before the character
+        let listed: Vec<_> = entries.iter()
+            .map(|e| (e.index, e.char.as_str(), e.switch.as_deref()))
+            .collect();
+        assert_eq!(listed, [(0, "a", None), (1, "b", None),
+                            (2, "", Some("numerals")), (2, "1", None),
+                            (3, " ", None), (4, "", Some("letters")),
+                            (4, "z", None)]);
+        assert_eq!(entries[3].poses, [[1, 0]]);
+
+        // each centre is that of its own figure
+        let signs: Vec<Sign> = encoded.into_iter()
+            .filter_map(|(_, s)| s.ok()).collect();
+        let (placed, _) = place(&signs, &params);
+        let figures = entries.iter().filter(|e| e.char != " ");
+        for (e, p) in figures.zip(placed.iter().filter(|p| p.sign != 4)) {
+            let (cg, pos) = (e.cg.as_ref().unwrap(), p.figures[0].get_pos());
+            assert_eq!((cg.x, cg.y), (pos.x, pos.y));
+        }
+
+        // the boxes hold every pixel drawn, touching them on all sides
+        let (pixels, _) = render(&signs, &params);
+        let width = params.width as i32;
+        let drawn = |x: i32, y: i32| {
+            pixels[(y * width + x) as usize] != params.palette.background
+        };
+        let boxes: Vec<_> = entries.iter().filter(|e| e.char != " ")
+            .map(|e| e.bbox.as_ref().unwrap()).collect();
+        for b in boxes.iter() {
+            assert!(b.x >= 0 && b.y >= 0 && b.x + b.w <= width
+                    && b.y + b.h <= params.height as i32);
+            let (x1, y1) = (b.x + b.w - 1, b.y + b.h - 1);
+            assert!((b.y..=y1).any(|y| drawn(b.x, y)));
+            assert!((b.y..=y1).any(|y| drawn(x1, y)));
+            assert!((b.x..=x1).any(|x| drawn(x, b.y)));
+            assert!((b.x..=x1).any(|x| drawn(x, y1)));
+        }
+        for y in 0..params.height as i32 {
+            for x in (0..width).filter(|&x| drawn(x, y)) {
+                assert!(boxes.iter().any(|b| b.x <= x && x < b.x + b.w
+                                         && b.y <= y && y < b.y + b.h));
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(quote("a"), "a");
+        assert_eq!(quote(","), "\",\"");
+        assert_eq!(quote("\""), "\"\"\"\"");
+        assert_eq!(quote("\n"), "\"\n\"");
+    }
+}
@@ -1,18 +1,66 @@
+mod alphabet;
+mod atlas;
+mod canvas;
+mod chunks;
+mod glyphs;
+mod layout;
+mod metadata;
+mod pdf;
+mod raster;
+mod sprite;
 mod stickman;
+mod stroke;
+mod svg;
 
 extern crate clap;
-use clap::{Arg, App};
-use image::{ImageError, Rgb, RgbImage};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use image::{Delay, DynamicImage, Frame, ImageBuffer, ImageError,
+            ImageOutputFormat, Rgba};
+use image::codecs::gif::{GifEncoder, Repeat};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::thread;
+use alphabet::*;
+use atlas::SpriteMode;
+use pdf::{can_write, text_width, Pdf};
+use raster::{Buffer, RasterCanvas};
+use canvas::Canvas;
+use layout::{draw_grid, layout, place, Align, Grid, Layout, Placed, VAlign,
+             Writing};
 use stickman::*;
+use stroke::{Cap, Join};
+use svg::Svg;
 
 const DEF_HEIGHT : u32 = 400;
 const DEF_WIDTH : u32 = 600;
+const FRAME_DELAY_MS : u32 = 1000;
+const DEF_MARGIN : f64 = 36.0;
+const DEF_OVERLAP : f64 = 28.346;
+const DEF_DPI : f64 = 96.0;
+const INCH_MM : f64 = 25.4;
+const INCH_PT : f64 = 72.0;
+const TEXT_SIZE : f64 = 10.0;
 
 pub enum SmphrError {
     NoData,
     InvalidData,
     ShouldNotAppear,
     NoPath,
+    InvalidAlphabet(AlphabetError),
+    InvalidColor(String),
+    InvalidPage(String),
+    InvalidFlagSize(String),
+    InvalidPageText(String),
+    FlagColor(String),
+    NotPng(String),
+    InvalidTemplate(String),
+    SameName(String),
+    NoFormat,
+    NotGif,
+    NoSpritePath,
+    CouldNotRead(std::io::Error),
     CouldNotWrite(ImageError)
 }
 
@@ -22,7 +70,31 @@ impl std::fmt::Display for SmphrError {
             Self::NoData => write!(f, "No input provided"),
             Self::InvalidData => write!(f, "No valid character in input"),
             Self::NoPath => write!(f, "No path providen for output file"),
-            Self::CouldNotWrite(e) => write!(f, "Could not write output file:\
+            Self::NoFormat => write!(f, "No format given for the standard \
+output, expected --format png, pbm, ppm, svg, gif or pdf"),
+            Self::NotGif => write!(f, "--strokes animate writes an animated \
+GIF, expected a path ending in .gif or --format gif"),
+            Self::NoSpritePath => write!(f, "--sprites writes files, not the \
+standard output, expected a path"),
+            Self::InvalidAlphabet(e) => write!(f, "Invalid alphabet: {}", e),
+            Self::InvalidColor(s) => write!(f, "Invalid colour '{}', \
+expected a name, #rrggbb, #rrggbbaa or 'transparent'", s),
+            Self::InvalidPage(s) => write!(f, "Invalid page size '{}', \
+expected a4, a3, a5, letter, legal or WIDTHxHEIGHT", s),
+            Self::InvalidFlagSize(s) => write!(f, "Invalid flag size '{}', \
+expected a whole number of pixels, 0 or more", s),
+            Self::InvalidPageText(s) => write!(f, "Cannot write '{}' on a \
+PDF page, only Latin-1 characters can be", s),
+            Self::FlagColor(s) => write!(f, "--{} and --flags custom go \
+together", s),
+            Self::NotPng(s) => write!(f, "'{}' is not a PNG file", s),
+            Self::InvalidTemplate(s) => write!(f, "Invalid output path '{}', \
+a batch needs {{n}} or {{name}} in it so that each image has its own", s),
+            Self::SameName(s) => write!(f, "Several files of the batch are \
+named '{}', expected {{n}} in the output path to tell them apart", s),
+            Self::CouldNotRead(e) => write!(f, "Could not read input file: {}",
+                                            e),
+            Self::CouldNotWrite(e) => write!(f, "Could not write output file: \
 {}", e),
             Self::ShouldNotAppear => write!(f, "SHOULD NOT BE PRINTED"),
         }
@@ -35,57 +107,613 @@ impl std::convert::From<StickmanError> for SmphrError {
     }
 }
 
+impl std::convert::From<AlphabetError> for SmphrError {
+    fn from(e: AlphabetError) -> SmphrError {
+        SmphrError::InvalidAlphabet(e)
+    }
+}
+
+/// How to render characters spelled with several poses.
+#[derive(Clone, Copy, PartialEq)]
+pub enum StrokeMode {
+    /// All the poses side by side.
+    Strip,
+    /// One pose per frame of an animated GIF.
+    Animate,
+}
+
+/// Encoding of the output, given by --format or the extension of its path.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Png,
+    Pdf,
+    Svg,
+    /// Netpbm bitmap, black where the image is dark.
+    Pbm,
+    /// Netpbm pixmap, over white where the image is transparent.
+    Ppm,
+    /// GIF, animated when the poses are.
+    Gif,
+    /// Left to the image crate, after the extension.
+    Other,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png),
+            "pdf" => Some(Format::Pdf),
+            "svg" => Some(Format::Svg),
+            "pbm" => Some(Format::Pbm),
+            "ppm" => Some(Format::Ppm),
+            "gif" => Some(Format::Gif),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &str) -> Format {
+        Path::new(path).extension()
+            .and_then(|e| Format::from_name(&e.to_string_lossy()))
+            .unwrap_or(Format::Other)
+    }
+}
+
+/// Where the figures are seen from, which decides on which side of the
+/// image each arm is drawn.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Viewpoint {
+    /// Facing the signaller, as the receiver sees them.
+    Observer,
+    /// From behind the signaller.
+    Signaller,
+}
+
+/// Colours of every part of the image.
+pub struct Palette {
+    pub background:   Rgba<u8>,
+    pub body:         Rgba<u8>,
+    pub head:         Rgba<u8>,
+    /// Upper hoist of the flag, the half along the pole from its top.
+    pub flag:         Rgba<u8>,
+    /// Lower fly of the flag, the half away from the pole.
+    pub flag_fly:     Rgba<u8>,
+    pub flag_outline: Rgba<u8>,
+}
+
+impl Palette {
+    fn default() -> Palette {
+        Palette {
+            background:   Rgba([255, 255, 255, 255]),
+            body:         Rgba([0, 0, 0, 255]),
+            head:         Rgba([0, 0, 0, 255]),
+            flag:         Rgba([255, 0, 0, 255]),
+            flag_fly:     Rgba([255, 255, 0, 255]),
+            flag_outline: Rgba([0, 0, 0, 255]),
+        }
+    }
+
+    /* Flags used at sea are red in the upper hoist and yellow, on land
+       white and blue. */
+    fn set_flags(&mut self, preset: &str) {
+        let (hoist, fly) = match preset {
+            "land" => ([255, 255, 255, 255], [0, 0, 160, 255]),
+            _ => ([255, 0, 0, 255], [255, 255, 0, 255]),
+        };
+        self.flag = Rgba(hoist);
+        self.flag_fly = Rgba(fly);
+    }
+
+    /* Whether the image needs an alpha channel. */
+    fn is_opaque(&self) -> bool {
+        [self.background, self.body, self.head, self.flag, self.flag_fly,
+         self.flag_outline].iter().all(|c| c[3] == 255)
+    }
+}
+
+/// Parses a colour given by name, as #rgb, #rrggbb or #rrggbbaa, or the
+/// word 'transparent'.
+pub fn parse_color(s: &str) -> Result<Rgba<u8>, SmphrError> {
+    let named = match s.to_ascii_lowercase().as_str() {
+        "transparent" => Some([0, 0, 0, 0]),
+        "white"  => Some([255, 255, 255, 255]),
+        "black"  => Some([0, 0, 0, 255]),
+        "red"    => Some([255, 0, 0, 255]),
+        "yellow" => Some([255, 255, 0, 255]),
+        "blue"   => Some([0, 0, 255, 255]),
+        "navy"   => Some([0, 0, 128, 255]),
+        "green"  => Some([0, 128, 0, 255]),
+        "grey" | "gray" => Some([128, 128, 128, 255]),
+        _ => None,
+    };
+    if let Some(c) = named {
+        return Ok(Rgba(c));
+    }
+
+    let invalid = || SmphrError::InvalidColor(s.to_string());
+    let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap();
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    match hex.len() {
+        3 => Ok(Rgba([digit(0) * 17, digit(1) * 17, digit(2) * 17, 255])),
+        6 => Ok(Rgba([byte(0), byte(2), byte(4), 255])),
+        8 => Ok(Rgba([byte(0), byte(2), byte(4), byte(6)])),
+        _ => Err(invalid()),
+    }
+}
+
+/// Pages of a PDF, in points, and the text written on their margins.
+pub struct Page {
+    pub width:  f64,
+    pub height: f64,
+    pub margin: f64,
+    /// Lines written above and under the stickmen, where {page} and {pages}
+    /// stand for the page number and the number of pages, {row} and
+    /// {column} for the place of the page on a poster.
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    /// Whether the whole image is split across pages, to be put together.
+    pub poster: bool,
+    /// Width of the strip a page of a poster shares with the next one.
+    pub overlap: f64,
+}
+
+impl Page {
+    fn default() -> Page {
+        Page {
+            width: 595.276,
+            height: 841.89,
+            margin: DEF_MARGIN,
+            header: None,
+            footer: None,
+            poster: false,
+            overlap: DEF_OVERLAP,
+        }
+    }
+
+    /* Header or footer with the fields, such as page, filled in. */
+    fn fill_in(text: &Option<String>, fields: &[(&str, usize)])
+        -> Option<String> {
+        text.as_ref().map(|t| fields.iter().fold(t.clone(), |t, (k, v)| {
+            t.replace(&format!("{{{}}}", k), &v.to_string())
+        }))
+    }
+
+    /* Writes the header and the footer in the middle of the margins. */
+    fn write_text(&self, pdf: &mut Pdf, fields: &[(&str, usize)],
+                  color: Rgba<u8>) {
+        let (top, bottom) = ((self.margin + TEXT_SIZE) / 2.0,
+                             self.height - (self.margin - TEXT_SIZE) / 2.0);
+        for (text, y) in [(&self.header, top), (&self.footer, bottom)] {
+            if let Some(t) = Page::fill_in(text, fields) {
+                let x = (self.width - text_width(&t) * TEXT_SIZE) / 2.0;
+                pdf.text(x, y, TEXT_SIZE, &t, color);
+            }
+        }
+    }
+}
+
+/// Parses a length such as 2cm, 12mm, 1.5in, 10pt or 300px into pixels at
+/// the given resolution, a bare number being in the given unit.
+pub fn parse_length(s: &str, bare: &str, dpi: f64) -> Option<f64> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let value = s[..split].trim_end().parse::<f64>().ok()?;
+    let unit = match &s[split..] { "" => bare, u => u };
+    let inches = match unit.to_ascii_lowercase().as_str() {
+        "px" => return Some(value),
+        "in" => value,
+        "mm" => value / INCH_MM,
+        "cm" => value * 10.0 / INCH_MM,
+        "pt" => value / INCH_PT,
+        _ => return None,
+    };
+    Some(inches * dpi)
+}
+
+/// Parses a page size given by name, such as a4 or letter, or as
+/// WIDTHxHEIGHT, each length in points unless it has a unit.
+pub fn parse_page(s: &str, dpi: f64) -> Result<(f64, f64), SmphrError> {
+    match s.to_ascii_lowercase().as_str() {
+        "a3" => return Ok((841.89, 1190.551)),
+        "a4" => return Ok((595.276, 841.89)),
+        "a5" => return Ok((419.528, 595.276)),
+        "letter" => return Ok((612.0, 792.0)),
+        "legal" => return Ok((612.0, 1008.0)),
+        _ => {},
+    }
+    let invalid = || SmphrError::InvalidPage(s.to_string());
+    // the x between the lengths is followed by a number, unlike that of px
+    let (i, _) = s.char_indices().find(|(i, c)| {
+        c.eq_ignore_ascii_case(&'x')
+            && s[i + 1..].starts_with(|d: char| d.is_ascii_digit() || d == '.')
+    }).ok_or_else(invalid)?;
+    let points = |l| parse_length(l, "pt", dpi).map(|px| px * INCH_PT / dpi);
+    match (points(&s[..i]), points(&s[i + 1..])) {
+        (Some(w), Some(h)) if w > 0.0 && h > 0.0 => Ok((w, h)),
+        _ => Err(invalid()),
+    }
+}
+
 pub struct SmphrParams {
     height: u32,
     width: u32,
     data: String,
     path: String,
+    alphabet: Alphabet,
+    strokes: StrokeMode,
+    view: Viewpoint,
+    palette: Palette,
+    flag_size: i32,
+    antialias: bool,
+    thickness: f64,
+    cap: Cap,
+    join: Join,
+    jobs: usize,
+    page: Page,
+    /// Resolution the lengths given in physical units are converted with.
+    dpi: f64,
+    /// Pixels for a unit of the figures.
+    scale: f64,
+    layout: Layout,
+    /// Whether the characters of the alphabet are written as sprites
+    /// instead of the text.
+    sprites: Option<SpriteMode>,
+    /// File telling where each character was placed, if any.
+    metadata: Option<String>,
+    format: Format,
 }
 
-impl SmphrParams {
-    pub fn from_args() -> Result<SmphrParams, SmphrError> {
+/// What the command line asks for.
+pub enum Command {
+    /// Draw the text.
+    Render(Box<SmphrParams>),
+    /// Draw each text to its own file.
+    Batch(Vec<SmphrParams>),
+    /// Print the text and settings recorded in a PNG file.
+    Inspect(String),
+}
+
+impl Command {
+    pub fn from_args() -> Result<Command, SmphrError> {
         let matches = app_args();
+        if let Some(m) = matches.subcommand_matches("inspect") {
+            let path = m.value_of("path").unwrap_or_default();
+            return Ok(Command::Inspect(path.to_string()));
+        }
+
+        let path = match matches.value_of("path") {
+            Some(s) => s.to_string(),
+            None => return Err(SmphrError::NoPath),
+        };
 
+        if let Some(batch) = matches.value_of("batch") {
+            if !path.contains("{n}") && !path.contains("{name}") {
+                return Err(SmphrError::InvalidTemplate(path));
+            }
+            let texts = read_batch(batch)?;
+            // files named alike, but for their extension, would overwrite
+            // each other's image
+            if !path.contains("{n}") {
+                let mut names = HashSet::new();
+                if let Some((name, _)) = texts.iter()
+                    .find(|(name, _)| !names.insert(name)) {
+                    return Err(SmphrError::SameName(name.clone()));
+                }
+            }
+            let mut jobs = Vec::new();
+            for (i, (name, data)) in texts.into_iter().enumerate() {
+                let n = (i + 1).to_string();
+                let fields = [("n", n.as_str()), ("name", name.as_str())];
+                let mut params = SmphrParams::from_matches(
+                    &matches, data, fill_template(&path, &fields))?;
+                params.metadata = params.metadata
+                    .map(|m| fill_template(&m, &fields));
+                jobs.push(params);
+            }
+            return Ok(Command::Batch(jobs));
+        }
+
+        let data = match (matches.value_of("input"), matches.value_of("data")) {
+            (Some(file), _) => read_input(file)?,
+            (None, Some("-")) => read_input("-")?,
+            (None, Some(s)) => s.to_string(),
+            (None, None) if matches.is_present("sprites") => String::new(),
+            (None, None) => return Err(SmphrError::NoData),
+        };
+        SmphrParams::from_matches(&matches, data, path)
+            .map(|p| Command::Render(Box::new(p)))
+    }
+}
+
+/* Text of a file, or of the standard input for -, without the line break
+   ending it. */
+fn read_input(path: &str) -> Result<String, SmphrError> {
+    let mut text = String::new();
+    let read = if path == "-" {
+        io::stdin().read_to_string(&mut text).map(|_| ())
+    } else {
+        fs::read_to_string(path).map(|t| text = t)
+    };
+    read.map_err(SmphrError::CouldNotRead)?;
+    let text = text.replace("\r\n", "\n");
+    Ok(text.strip_suffix('\n').unwrap_or(&text).to_string())
+}
+
+/* Texts of a batch with their names: each file of a directory, by order of
+   name, named after it, or each line of a file, or of the standard input
+   for -, numbered. Blank lines are left out. */
+fn read_batch(path: &str) -> Result<Vec<(String, String)>, SmphrError> {
+    if path != "-" && Path::new(path).is_dir() {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(path).map_err(SmphrError::CouldNotRead)? {
+            let file = entry.map_err(SmphrError::CouldNotRead)?.path();
+            let hidden = file.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if file.is_file() && !hidden { files.push(file); }
+        }
+        files.sort();
+        return files.iter().map(|f| {
+            let name = f.file_stem().map_or(String::new(),
+                                            |s| s.to_string_lossy().into());
+            read_input(&f.to_string_lossy()).map(|text| (name, text))
+        }).collect();
+    }
+
+    Ok(read_input(path)?.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| ((i + 1).to_string(), line.to_string()))
+        .collect())
+}
+
+/* Output path of a batch with the fields, such as n, filled in. */
+fn fill_template(template: &str, fields: &[(&str, &str)]) -> String {
+    fields.iter().fold(template.to_string(), |t, (k, v)| {
+        t.replace(&format!("{{{}}}", k), v)
+    })
+}
+
+impl SmphrParams {
+    fn from_matches(matches: &ArgMatches, data: String, path: String)
+        -> Result<SmphrParams, SmphrError> {
         let mut height = DEF_HEIGHT;
         let mut width = DEF_WIDTH;
 
-        if let Some(s) = matches.value_of("height") {
-            height = match s.parse::<u32>() {
-                Ok(v) => v,
-                Err(_) => DEF_HEIGHT,
+        let mut dpi = DEF_DPI;
+        if let Some(s) = matches.value_of("dpi") {
+            match s.parse::<f64>() {
+                Ok(v) if v > 0.0 => dpi = v,
+                _ => {},
             }
         }
+        let pixels = |s| match parse_length(s, "px", dpi) {
+            Some(v) if v >= 0.0 => Some(v.round() as u32),
+            _ => None,
+        };
+
+        if let Some(s) = matches.value_of("height") {
+            height = pixels(s).unwrap_or(DEF_HEIGHT);
+        }
 
         if let Some(s) = matches.value_of("width") {
-            width = match s.parse::<u32>() {
-                Ok(v) => v,
-                Err(_) => DEF_WIDTH,
-            }
+            width = pixels(s).unwrap_or(DEF_WIDTH);
         }
 
-        let data = match matches.value_of("data") {
-            Some(s) => s.to_string(),
-            None => return Err(SmphrError::NoData),
+        let sprites = match matches.value_of("sprites") {
+            Some("sheet") => Some(SpriteMode::Sheet),
+            Some("files") => Some(SpriteMode::Files),
+            _ => None,
         };
+        // a sheet comes with its atlas, and files with their directory
+        if sprites.is_some() && path == "-" {
+            return Err(SmphrError::NoSpritePath);
+        }
 
-        let path = match matches.value_of("path") {
-            Some(s) => s.to_string(),
-            None => return Err(SmphrError::NoPath),
+        let alphabet = match matches.value_of("alphabet") {
+            None | Some("auto") => Alphabet::detect(&data),
+            Some(name) => match Alphabet::builtin(name) {
+                Some(a) => a,
+                None => Alphabet::from_file(name)?,
+            },
+        };
+
+        let strokes = match matches.value_of("strokes") {
+            Some("animate") => StrokeMode::Animate,
+            _ => StrokeMode::Strip,
+        };
+
+        let view = match matches.value_of("view") {
+            Some("signaller") => Viewpoint::Signaller,
+            _ => Viewpoint::Observer,
         };
 
-        Ok(SmphrParams::from_values(height, width, data, path))
+        let mut palette = Palette::default();
+        let flags = matches.value_of("flags").unwrap_or("sea");
+        palette.set_flags(flags);
+        // custom flags take both their colours, and only they do
+        for name in ["flag", "flag-fly"] {
+            if (flags == "custom") != matches.is_present(name) {
+                return Err(SmphrError::FlagColor(name.to_string()));
+            }
+        }
+        for (name, color) in [("background", &mut palette.background),
+                              ("body", &mut palette.body),
+                              ("head", &mut palette.head),
+                              ("flag", &mut palette.flag),
+                              ("flag-fly", &mut palette.flag_fly),
+                              ("flag-outline", &mut palette.flag_outline)] {
+            if let Some(s) = matches.value_of(name) {
+                *color = parse_color(s)?;
+            }
+        }
+
+        let mut params = SmphrParams::from_values(height, width, data, path,
+                                                  alphabet);
+        params.palette = palette;
+        params.dpi = dpi;
+        match matches.value_of("format").and_then(Format::from_name) {
+            Some(f) => params.format = f,
+            None if params.path == "-" => return Err(SmphrError::NoFormat),
+            None => {},
+        }
+        if let Some(s) = matches.value_of("figure-height") {
+            match parse_length(s, "px", dpi) {
+                Some(v) if v > 0.0 => params.scale = v / STICK_HEIGHT as f64,
+                _ => {},
+            }
+        }
+        params.antialias = matches.is_present("antialias");
+        if let Some(s) = matches.value_of("thickness") {
+            match s.parse::<f64>() {
+                Ok(v) if v > 0.0 => params.thickness = v,
+                _ => {},
+            }
+        }
+        params.cap = match matches.value_of("cap") {
+            Some("butt") => Cap::Butt,
+            Some("square") => Cap::Square,
+            _ => Cap::Round,
+        };
+        params.join = match matches.value_of("join") {
+            Some("miter") => Join::Miter,
+            _ => Join::Round,
+        };
+        if let Some(s) = matches.value_of("jobs") {
+            match s.parse::<usize>() {
+                Ok(v) if v > 0 => params.jobs = v,
+                _ => {},
+            }
+        }
+        if let Some(s) = matches.value_of("flag-size") {
+            match s.parse::<i32>() {
+                Ok(v) if v >= 0 => params.flag_size = v,
+                _ => return Err(SmphrError::InvalidFlagSize(s.to_string())),
+            }
+        }
+        if let Some(s) = matches.value_of("page") {
+            let (w, h) = parse_page(s, dpi)?;
+            params.page.width = w;
+            params.page.height = h;
+        }
+        // on a PDF, the margins are those of the pages
+        let pdf = params.format == Format::Pdf;
+        if let Some(s) = matches.value_of("margin") {
+            match parse_length(s, if pdf { "pt" } else { "px" }, dpi) {
+                Some(v) if v >= 0.0 && pdf => {
+                    params.page.margin = v * INCH_PT / dpi;
+                },
+                Some(v) if v >= 0.0 => params.layout.margin = v.round() as i32,
+                _ => {},
+            }
+        }
+        for (name, length) in [("gap", &mut params.layout.gap),
+                               ("row-gap", &mut params.layout.row_gap)] {
+            if let Some(v) = matches.value_of(name).and_then(pixels) {
+                *length = v as i32;
+            }
+        }
+        if let Some(s) = matches.value_of("word-spacing") {
+            params.layout.word = pixels(s).map(|v| v as i32);
+        }
+        params.layout.align = match matches.value_of("align") {
+            Some("centre") => Align::Centre,
+            Some("right") => Align::Right,
+            Some("justify") => Align::Justify,
+            _ => Align::Left,
+        };
+        params.layout.valign = match matches.value_of("valign") {
+            Some("centre") => VAlign::Centre,
+            Some("bottom") => VAlign::Bottom,
+            _ => VAlign::Top,
+        };
+        params.layout.writing = match matches.value_of("writing") {
+            Some("vertical") => Writing::Vertical,
+            _ => Writing::Horizontal,
+        };
+        params.layout.rtl = matches.value_of("direction") == Some("rtl");
+        if matches.is_present("grid") {
+            let count = |name| matches.value_of(name)
+                .and_then(|s| s.parse::<usize>().ok())
+                .filter(|n| *n > 0);
+            params.layout.grid = Some(Grid {
+                columns: count("columns"),
+                borders: matches.is_present("borders"),
+                labels: matches.is_present("labels"),
+                blank: count("blank"),
+            });
+        }
+        let texts = ["header", "footer"].iter()
+            .filter_map(|n| matches.value_of(n));
+        for s in texts {
+            if !can_write(s) {
+                return Err(SmphrError::InvalidPageText(s.to_string()));
+            }
+        }
+        params.page.header = matches.value_of("header").map(str::to_string);
+        params.page.footer = matches.value_of("footer").map(str::to_string);
+        params.page.poster = matches.is_present("poster");
+        if let Some(s) = matches.value_of("overlap") {
+            match parse_length(s, "pt", dpi) {
+                Some(v) if v >= 0.0 => params.page.overlap = v * INCH_PT / dpi,
+                _ => {},
+            }
+        }
+        if pdf && !params.page.poster {
+            params.fit_page();
+        }
+        // only a GIF holds the frames of the poses
+        if strokes == StrokeMode::Animate && sprites.is_none()
+            && params.format != Format::Gif {
+            return Err(SmphrError::NotGif);
+        }
+        params.strokes = strokes;
+        params.view = view;
+        params.sprites = sprites;
+        params.metadata = matches.value_of("metadata").map(String::from);
+        Ok(params)
     }
 
-    fn from_values(height: u32, width: u32, data: String, path: String)
-        -> SmphrParams {
+    fn from_values(height: u32, width: u32, data: String, path: String,
+                   alphabet: Alphabet) -> SmphrParams {
+        let format = Format::from_path(&path);
         SmphrParams {
             height, 
             width,
             data,
             path,
+            alphabet,
+            strokes: StrokeMode::Strip,
+            view: Viewpoint::Observer,
+            palette: Palette::default(),
+            flag_size: FLAG_LENGTH,
+            antialias: false,
+            thickness: 1.0,
+            cap: Cap::Round,
+            join: Join::Round,
+            jobs: default_jobs(),
+            page: Page::default(),
+            dpi: DEF_DPI,
+            scale: 1.0,
+            layout: Layout::default(),
+            sprites: None,
+            metadata: None,
+            format,
         }
     }
 
+    /* On a PDF, the stickmen are laid out within the margins, in pixels at
+       the resolution. */
+    fn fit_page(&mut self) {
+        let page = &self.page;
+        let px = self.dpi / INCH_PT;
+        let width = (page.width - 2.0 * page.margin) * px;
+        let height = (page.height - 2.0 * page.margin) * px;
+        self.width = width.max(0.0) as u32;
+        self.height = height.max(0.0) as u32;
+    }
+
     #[allow(dead_code)]
     fn default() -> SmphrParams {
         SmphrParams {
@@ -93,100 +721,1109 @@ impl SmphrParams {
             width: DEF_WIDTH,
             data: String::new(),
             path: String::new(),
+            alphabet: Alphabet::latin(),
+            strokes: StrokeMode::Strip,
+            view: Viewpoint::Observer,
+            palette: Palette::default(),
+            flag_size: FLAG_LENGTH,
+            antialias: false,
+            thickness: 1.0,
+            cap: Cap::Round,
+            join: Join::Round,
+            jobs: default_jobs(),
+            page: Page::default(),
+            dpi: DEF_DPI,
+            scale: 1.0,
+            layout: Layout::default(),
+            sprites: None,
+            metadata: None,
+            format: Format::Png,
         }
     }
 }
 
+/* One thread per processor. */
+fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
 fn app_args() -> clap::ArgMatches<'static> {
     App::new("smphr")
-        .version("0.1.0")
+        .version(env!("CARGO_PKG_VERSION"))
         .author("François Straet")
         .about("Generate semaphore images from text")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        // the text, coming after the path, is never taken for a subcommand
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .setting(AppSettings::DisableHelpSubcommand)
+        .subcommand(SubCommand::with_name("inspect")
+            .about("Print the text and settings a PNG file was made with")
+            .arg(Arg::with_name("path")
+                .help("PNG file written by smphr")
+                .required(true)
+                .index(1)))
         .arg(Arg::with_name("path")
-            .help("Path of output image to be written")
+            .help("Path of output image to be written, or - to write it to \
+the standard output with --format")
             .required(true)
             .index(1))
+        .arg(Arg::with_name("format")
+            .help("Format of the output, instead of that of the extension of \
+its path. pbm is black and white.")
+            .long("format")
+            .possible_values(&["png", "pbm", "ppm", "svg", "gif", "pdf"])
+            .takes_value(true))
         .arg(Arg::with_name("data")
-            .help("The text to be translated in smphr")
-            .required(true)
+            .help("The text to be translated in smphr, or - to read it from \
+the standard input")
+            .required_unless_one(&["sprites", "input", "batch"])
             .index(2))
+        .arg(Arg::with_name("input")
+            .help("Read the text from FILE, or from the standard input for -.")
+            .long("input")
+            .value_name("FILE")
+            .conflicts_with("data")
+            .takes_value(true))
+        .arg(Arg::with_name("batch")
+            .help("Draw each line of FILE, or each file of the directory, to \
+its own image. {n} in the output path stands for the number of the text, \
+{name} for the name of its file, or its line number.")
+            .long("batch")
+            .value_name("FILE")
+            .conflicts_with_all(&["data", "input", "sprites"])
+            .takes_value(true))
+        .arg(Arg::with_name("sprites")
+            .help("Write every character of the alphabet as a sprite instead \
+of the text: 'sheet' packs them into the output image with a JSON atlas of \
+the same name, 'files' writes one PNG per character into the output \
+directory with atlas.json.")
+            .long("sprites")
+            .possible_values(&["sheet", "files"])
+            .takes_value(true))
+        .arg(Arg::with_name("metadata")
+            .help("Also write where each character of the text was placed, \
+with its poses, centre, bounding box, row and page, or why it was skipped: \
+as CSV when FILE ends in .csv, as JSON otherwise.")
+            .long("metadata")
+            .value_name("FILE")
+            .takes_value(true))
         .arg(Arg::with_name("height")
-            .help("Set the output image's height. When incorrect, use default 400.")
+            .help("Set the output image's height, in pixels or with a unit \
+such as 10cm, 100mm or 4in. When incorrect, use default 400.")
             .short("h")
             .long("height")
             .takes_value(true))
         .arg(Arg::with_name("width")
-            .help("Set the output image's width. When incorrect, use default 600.")
+            .help("Set the output image's width, in pixels or with a unit \
+such as 15cm, 150mm or 6in. When incorrect, use default 600.")
             .short("w")
             .long("width")
             .takes_value(true))
+        .arg(Arg::with_name("alphabet")
+            .help("Alphabet to use: a built-in one (latin, ja), 'auto' to \
+detect it from the text, or a TOML or JSON definition file. Defaults to auto.")
+            .short("a")
+            .long("alphabet")
+            .takes_value(true))
+        .arg(Arg::with_name("strokes")
+            .help("How to render characters spelled with several poses, \
+such as kana: 'strip' draws the poses side by side, 'animate' writes an \
+animated GIF showing one pose per frame, and so needs a .gif path or \
+--format gif.")
+            .long("strokes")
+            .possible_values(&["strip", "animate"])
+            .default_value("strip"))
+        .arg(Arg::with_name("view")
+            .help("Where the figures are seen from: 'observer' faces the \
+signaller, 'signaller' stands behind them, swapping left and right.")
+            .long("view")
+            .possible_values(&["observer", "signaller"])
+            .default_value("observer"))
+        .arg(Arg::with_name("background")
+            .help("Background colour: a name, #rrggbb, #rrggbbaa, or \
+'transparent' to write an RGBA image. Defaults to white.")
+            .long("background")
+            .takes_value(true))
+        .arg(Arg::with_name("body")
+            .help("Colour of the body and arms. Defaults to black.")
+            .long("body")
+            .takes_value(true))
+        .arg(Arg::with_name("head")
+            .help("Colour of the head. Defaults to black.")
+            .long("head")
+            .takes_value(true))
+        .arg(Arg::with_name("flags")
+            .help("Flag colours: 'sea' for red and yellow, 'land' for white \
+and blue, 'custom' for the colours given by --flag and --flag-fly.")
+            .long("flags")
+            .possible_values(&["sea", "land", "custom"])
+            .default_value("sea"))
+        .arg(Arg::with_name("flag")
+            .help("Colour of the upper hoist of the flags, the half along the \
+pole from its top, with --flags custom.")
+            .long("flag")
+            .takes_value(true))
+        .arg(Arg::with_name("flag-fly")
+            .help("Colour of the lower fly of the flags, the half away from \
+the pole, with --flags custom.")
+            .long("flag-fly")
+            .takes_value(true))
+        .arg(Arg::with_name("flag-size")
+            .help("Side of the flags, in pixels at the figures' own size, 0 \
+or more. Defaults to 10.")
+            .long("flag-size")
+            .takes_value(true))
+        .arg(Arg::with_name("antialias")
+            .help("Smooth the edges of lines, heads and flags by blending \
+them into the background.")
+            .long("antialias"))
+        .arg(Arg::with_name("thickness")
+            .help("Multiply the thickness of the body, legs and head. When \
+incorrect, use default 1.")
+            .long("thickness")
+            .takes_value(true))
+        .arg(Arg::with_name("cap")
+            .help("Shape of the ends of the body and legs.")
+            .long("cap")
+            .possible_values(&["round", "square", "butt"])
+            .default_value("round"))
+        .arg(Arg::with_name("join")
+            .help("Shape of the corner where the legs meet.")
+            .long("join")
+            .possible_values(&["round", "miter"])
+            .default_value("round"))
+        .arg(Arg::with_name("jobs")
+            .help("Number of threads drawing the image. When incorrect, use \
+one per processor.")
+            .short("j")
+            .long("jobs")
+            .takes_value(true))
+        .arg(Arg::with_name("gap")
+            .help("Space between two stickmen of a row, in pixels or with a \
+unit. Defaults to 0.")
+            .long("gap")
+            .takes_value(true))
+        .arg(Arg::with_name("row-gap")
+            .help("Space between two rows of stickmen, in pixels or with a \
+unit. Defaults to 0.")
+            .long("row-gap")
+            .takes_value(true))
+        .arg(Arg::with_name("word-spacing")
+            .help("Width of a space between words, in pixels or with a unit. \
+Defaults to the width of a stickman.")
+            .long("word-spacing")
+            .takes_value(true))
+        .arg(Arg::with_name("align")
+            .help("Where the stickmen of a row stand; 'justify' spreads them \
+over the whole width but for the last row of a paragraph.")
+            .long("align")
+            .possible_values(&["left", "centre", "right", "justify"])
+            .default_value("left"))
+        .arg(Arg::with_name("valign")
+            .help("Where the rows stand on the image.")
+            .long("valign")
+            .possible_values(&["top", "centre", "bottom"])
+            .default_value("top"))
+        .arg(Arg::with_name("writing")
+            .help("Whether the stickmen are laid out in rows, or in columns \
+from top to bottom. In columns, --align places the figures within a column \
+and --valign the block of columns, top standing for the side of the first \
+one.")
+            .long("writing")
+            .possible_values(&["horizontal", "vertical"])
+            .default_value("horizontal"))
+        .arg(Arg::with_name("direction")
+            .help("Whether rows start on the left or on the right, and \
+columns go from left to right or from right to left. Alignments are turned \
+over with them.")
+            .long("direction")
+            .possible_values(&["ltr", "rtl"])
+            .default_value("ltr"))
+        .arg(Arg::with_name("grid")
+            .help("Place each character in a cell of a fixed grid, from the \
+top left corner, as on a worksheet.")
+            .long("grid"))
+        .arg(Arg::with_name("columns")
+            .help("Number of columns of the grid, at most as many as fit. \
+When incorrect, use as many as fit.")
+            .long("columns")
+            .takes_value(true)
+            .requires("grid"))
+        .arg(Arg::with_name("borders")
+            .help("Outline the cells of the grid.")
+            .long("borders")
+            .requires("grid"))
+        .arg(Arg::with_name("labels")
+            .help("Name the columns of the grid with letters and number its \
+rows, so that cells read A1, B3 and so on.")
+            .long("labels")
+            .requires("grid"))
+        .arg(Arg::with_name("blank")
+            .help("Leave the cell of every Nth character empty, to be filled \
+in; 1 leaves them all empty.")
+            .long("blank")
+            .takes_value(true)
+            .requires("grid"))
+        .arg(Arg::with_name("dpi")
+            .help("Resolution the lengths in physical units are converted \
+with, also recorded in PNG files. When incorrect, use default 96.")
+            .long("dpi")
+            .takes_value(true))
+        .arg(Arg::with_name("figure-height")
+            .help("Height of a row of stickmen, in pixels or with a unit such \
+as 2cm. The figures, their lines and flags are scaled to it. Defaults to 81 \
+pixels.")
+            .long("figure-height")
+            .takes_value(true))
+        .arg(Arg::with_name("page")
+            .help("Page size of a PDF output: a4, a3, a5, letter, legal or \
+WIDTHxHEIGHT in points or with units, such as 20cmx20cm. The page replaces \
+the width and height. Defaults to a4.")
+            .long("page")
+            .takes_value(true))
+        .arg(Arg::with_name("margin")
+            .help("Margin around the stickmen, in pixels or with a unit \
+such as 15mm. On a PDF, margin of the pages, in points by default. When \
+incorrect, use default 0, or 36 points on a PDF.")
+            .long("margin")
+            .takes_value(true))
+        .arg(Arg::with_name("header")
+            .help("Text written at the top of every page of a PDF, where \
+{page} and {pages} stand for the page number and count. Only Latin-1 \
+characters can be written.")
+            .long("header")
+            .takes_value(true))
+        .arg(Arg::with_name("footer")
+            .help("Text written at the bottom of every page of a PDF, like \
+the header.")
+            .long("footer")
+            .takes_value(true))
+        .arg(Arg::with_name("poster")
+            .help("Split the image of the given width and height across as \
+many pages of a PDF as it takes, with cut marks and a map of where each page \
+goes.")
+            .long("poster"))
+        .arg(Arg::with_name("overlap")
+            .help("Width of the strip a page of a poster shares with the \
+next one, in points or with a unit such as 1cm. When incorrect, use default \
+1cm.")
+            .long("overlap")
+            .takes_value(true))
+        .arg(Arg::with_name("flag-outline")
+            .help("Outline colour of the flags. Defaults to black.")
+            .long("flag-outline")
+            .takes_value(true))
         .get_matches()
 }
 
-pub fn exec(params: SmphrParams) -> Result<(), SmphrError> {
+/* Draws the signs in a buffer of pixels, also telling whether the text had
+   to be cut because it did not fit. */
+fn render(signs: &[Sign], params: &SmphrParams) -> (Vec<Rgba<u8>>, bool) {
+    let (tabw, tabh) = (params.width as usize, params.height as usize);
+    let (groups, n) = layout(signs, params);
+    let cut = n < signs.len();
+    let mut tab = vec![params.palette.background; tabw * tabh];
+    draw_bands(&groups, params, &mut canvases(params), 0, &mut tab);
+    (tab, cut)
+}
 
-    // first detect invalid inputs:
-    let mut valid = true;
-    for c in params.data.chars() {
-        if c.is_alphanumeric() {
-            valid = true;
-            break;
-        }
+/* Writes the signs on as many pages as they take, also telling whether the
+   text had to be cut because a sign did not fit on a whole page. */
+fn write_pdf(signs: &[Sign], params: &SmphrParams)
+    -> Result<bool, ImageError> {
+
+    let (pages, n) = paginate(signs, params);
+    let page = &params.page;
+    let colors = &params.palette;
+    let count = pages.len();
+    let mut pdf = Pdf::new(page.width, page.height);
+    for (i, (_, placed)) in pages.into_iter().enumerate() {
+        pdf.new_page(colors.background);
+        let fields = [("page", i + 1), ("pages", count)];
+        page.write_text(&mut pdf, &fields, colors.body);
+
+        let groups: Vec<Vec<Stickman>> = placed.into_iter()
+            .map(|p| p.figures).collect();
+        pdf.save(page.margin, page.margin, INCH_PT / params.dpi);
+        draw_rows(&groups, params, &mut pdf, 0, params.height as i32);
+        pdf.restore();
     }
-    if !valid { return Err(SmphrError::InvalidData); }
+    save_pdf(pdf, params)?;
+    Ok(n < signs.len())
+}
 
-    let (tabw, tabh) = (params.width as usize, params.height as usize);
-    let mut vec_tab = vec![0 as u8; tabw * tabh];
-    let tab = vec_tab.as_mut_slice();
+/* Signs laid out page after page, each page with the index of its first
+   sign, with the number of signs that fit before one did not fit on a whole
+   page. */
+fn paginate(signs: &[Sign], params: &SmphrParams)
+    -> (Vec<(usize, Vec<Placed>)>, usize) {
+
+    let mut pages = Vec::new();
+    let mut start = 0;
+    while start < signs.len() {
+        let (placed, n) = place(&signs[start..], params);
+        if n == 0 { break; }
+        pages.push((start, placed));
+        start += n;
+    }
+    (pages, start)
+}
+
+/* Writes the image split into tiles as large as the inside of the margins,
+   each on its own page and overlapping the next ones, also telling whether
+   the text had to be cut. */
+fn write_poster(signs: &[Sign], params: &SmphrParams)
+    -> Result<bool, ImageError> {
+
+    let (groups, n) = layout(signs, params);
+    let page = &params.page;
+    let colors = &params.palette;
+    let px = params.dpi / INCH_PT;
+    let ((cols, rows), tiles) = tiles(params);
+
+    let mut pdf = Pdf::new(page.width, page.height);
+    for (i, &(x0, y0, w, h)) in tiles.iter().enumerate() {
+        let (row, col) = (i / cols, i % cols);
+        pdf.new_page(colors.background);
+        let fields = [("page", i + 1), ("pages", rows * cols),
+                      ("row", row + 1), ("column", col + 1)];
+        page.write_text(&mut pdf, &fields, colors.body);
+        draw_marks(&mut pdf, page, (col, row), (cols, rows), colors.body);
+
+        pdf.save(page.margin, page.margin, 1.0 / px);
+        pdf.clip(0.0, 0.0, w, h);
+        pdf.save(-x0, -y0, 1.0);
+        draw_rows(&groups, params, &mut pdf, y0.floor() as i32,
+                  (y0 + h).ceil() as i32);
+        pdf.restore();
+        pdf.restore();
+    }
+    save_pdf(pdf, params)?;
+    Ok(n < signs.len())
+}
+
+/* Part of the image on a page of a poster: origin and size, in pixels. */
+type Tile = (f64, f64, f64, f64);
+
+/* Columns and rows of a poster, and the tile on each of its pages, row
+   after row. Tiles are as large as the inside of the margins and start
+   where the overlap of the previous one does; the last ones keep what is
+   left of the image. */
+fn tiles(params: &SmphrParams) -> ((usize, usize), Vec<Tile>) {
 
-    let mut it = params.data.chars();
-    let mut prev = Stickman::first_from_letter('z')?;
+    let page = &params.page;
+    // tiles are laid out in pixels of the image, then scaled to points
+    let px = params.dpi / INCH_PT;
+    let tile = ((page.width - 2.0 * page.margin) * px,
+                (page.height - 2.0 * page.margin) * px);
+    let step = ((tile.0 - page.overlap * px).max(1.0),
+                (tile.1 - page.overlap * px).max(1.0));
+    let count = |size: u32, tile: f64, step: f64| {
+        1 + ((size as f64 - tile) / step).ceil().max(0.0) as usize
+    };
+    let (cols, rows) = (count(params.width, tile.0, step.0),
+                        count(params.height, tile.1, step.1));
+    let tiles = (0..rows).flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .map(|(row, col)| {
+            let (x0, y0) = (col as f64 * step.0, row as f64 * step.1);
+            (x0, y0, tile.0.min(params.width as f64 - x0),
+             tile.1.min(params.height as f64 - y0))
+        })
+        .collect();
+    ((cols, rows), tiles)
+}
+
+/* Cut marks at the corners of the inside of the margins, marks where the
+   next pages start, and a map of the poster with this page filled in. */
+fn draw_marks(pdf: &mut Pdf, page: &Page, (col, row): (usize, usize),
+              (cols, rows): (usize, usize), color: Rgba<u8>) {
+    for (from, to) in mark_lines(page, (col, row), (cols, rows)) {
+        pdf.line(from, to, 1.0, color);
+    }
 
-    loop {
-        let c = it.next().unwrap();
-        let k = Stickman::first_from_letter(c);
-        if let Ok(prev) = k {
-            prev.draw(tab, &params);
-            break;
+    // map in the top right corner, with the place of the page spelled out
+    let (cell, (mx, my)) = poster_map(page, (cols, rows));
+    for r in 0..rows {
+        for c in 0..cols {
+            let (x, y) = (mx + c as f64 * cell, my + r as f64 * cell);
+            let square = [(x, y), (x + cell, y), (x + cell, y + cell),
+                          (x, y + cell)];
+            if (r, c) == (row, col) {
+                pdf.polygon(&square, color);
+            } else {
+                for k in 0..4 {
+                    pdf.line(square[k], square[(k + 1) % 4], 1.0, color);
+                }
+            }
         }
     }
+    let label = format!("row {}, page {}", row + 1, col + 1);
+    let x = (mx - cell - text_width(&label) * TEXT_SIZE).max(0.0);
+    let m = page.margin;
+    pdf.text(x, (m + TEXT_SIZE) / 2.0, TEXT_SIZE, &label, color);
+}
 
-    while let Some(c) = it.next() {
-        match Stickman::from_letter_with_prev(c, prev.get_pos(), &params) {
-            Ok(s) => {
-                s.draw(tab, &params);
-                prev = s;
-            },
-            Err(e) => {
-                match e {
-                    StickmanError::VerticalOverflow => {
-                        println!("Vertical overflow, exiting loop.\
-\nThis will cut the input text.");
-                        break;
-                    },
-                    _ => {
-                        println!("Error creating stickman, {}.", e);
-                    }
+/* Lines of the cut marks of a page of a poster, and of the marks where the
+   next pages start. */
+fn mark_lines(page: &Page, (col, row): (usize, usize),
+              (cols, rows): (usize, usize)) -> Vec<((f64, f64), (f64, f64))> {
+    let m = page.margin;
+    let (x1, y1) = (page.width - m, page.height - m);
+    let (gap, len) = (m / 6.0, m / 2.0);
+    let mut lines = Vec::new();
+    for (x, dx) in [(m, -1.0), (x1, 1.0)] {
+        for (y, dy) in [(m, -1.0), (y1, 1.0)] {
+            lines.push(((x + dx * gap, y), (x + dx * (gap + len), y)));
+            lines.push(((x, y + dy * gap), (x, y + dy * (gap + len))));
+        }
+    }
+    // the overlap starts where the next page does, in from the edges
+    let (xo, yo) = (x1 - page.overlap, y1 - page.overlap);
+    if col + 1 < cols {
+        lines.push(((xo, m - gap), (xo, m - gap - len)));
+        lines.push(((xo, y1 + gap), (xo, y1 + gap + len)));
+    }
+    if row + 1 < rows {
+        lines.push(((m - gap, yo), (m - gap - len, yo)));
+        lines.push(((x1 + gap, yo), (x1 + gap + len, yo)));
+    }
+    lines
+}
+
+/* Size of a square of the map of a poster and the top left corner of the
+   map, kept on the page even when the margin is too small to hold it. */
+fn poster_map(page: &Page, (cols, rows): (usize, usize))
+    -> (f64, (f64, f64)) {
+    let m = page.margin;
+    let x1 = page.width - m;
+    let cell = (m / 2.0 / rows as f64).clamp(1.0, TEXT_SIZE)
+        .min(page.width / cols as f64);
+    let mx = (x1 - cell * cols as f64).max(0.0);
+    let my = ((m - cell * rows as f64) / 2.0).max(0.0);
+    (cell, (mx, my))
+}
+
+fn save_pdf(pdf: Pdf, params: &SmphrParams) -> Result<(), ImageError> {
+    let mut out = create(&params.path)?;
+    pdf.write(&mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/* Draws the signs as an SVG image, also telling whether the text had to be
+   cut. */
+fn write_svg(signs: &[Sign], params: &SmphrParams)
+    -> Result<bool, ImageError> {
+
+    let (groups, n) = layout(signs, params);
+    // printed at the resolution, as PNG files and PDF pages are
+    let pt = INCH_PT / params.dpi;
+    let size = (params.width as f64 * pt, params.height as f64 * pt);
+    let mut svg = Svg::new(params.width, params.height, size,
+                           params.palette.background);
+    draw_rows(&groups, params, &mut svg, 0, params.height as i32);
+    let mut out = create(&params.path)?;
+    svg.write(&mut out)?;
+    out.flush()?;
+    Ok(n < signs.len())
+}
+
+/* Writes the pixels as a binary Netpbm pixmap, or as a bitmap where dark
+   pixels are black. Transparent pixels are taken over white. */
+fn write_netpbm(tab: &[Rgba<u8>], params: &SmphrParams, bitmap: bool)
+    -> Result<(), ImageError> {
+
+    let mut out = create(&params.path)?;
+    let (w, h) = (params.width, params.height);
+    let over_white = |p: &Rgba<u8>| {
+        let a = p[3] as u32;
+        let c = |v: u8| ((v as u32 * a + 255 * (255 - a)) / 255) as u8;
+        [c(p[0]), c(p[1]), c(p[2])]
+    };
+    if bitmap {
+        write!(out, "P4\n{} {}\n", w, h)?;
+        let mut line = Vec::with_capacity((w as usize).div_ceil(8));
+        for row in tab.chunks(w.max(1) as usize) {
+            line.clear();
+            for byte in row.chunks(8) {
+                let mut bits = 0u8;
+                for (i, p) in byte.iter().enumerate() {
+                    let [r, g, b] = over_white(p);
+                    let luma = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+                    if luma < 128_000 { bits |= 0x80 >> i; }
                 }
+                line.push(bits);
             }
+            out.write_all(&line)?;
         }
+    } else {
+        write!(out, "P6\n{} {}\n255\n", w, h)?;
+        for row in tab.chunks(w.max(1) as usize) {
+            let line: Vec<u8> = row.iter().flat_map(over_white).collect();
+            out.write_all(&line)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/* File at the path, or the standard output for -. */
+fn create(path: &str) -> std::io::Result<Box<dyn Write>> {
+    if path == "-" {
+        Ok(Box::new(BufWriter::new(io::stdout())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+/* One canvas per job, each keeping its sprites from band to band. */
+fn canvases(params: &SmphrParams) -> Vec<RasterCanvas<Buffer>> {
+    (0..params.jobs).map(|_| {
+        let buffer = Buffer::new(params.width as usize, 0,
+                                 params.palette.background);
+        RasterCanvas::new(buffer, params.antialias)
+    }).collect()
+}
+
+/* Draws the rows of the image from top into tab, cut into bands of one row
+   of stickmen shared between the canvases, each drawn by its own thread
+   into its own part of tab. */
+fn draw_bands(groups: &[Vec<Stickman>], params: &SmphrParams,
+              canvases: &mut [RasterCanvas<Buffer>], top: usize,
+              tab: &mut [Rgba<u8>]) {
+    let rows = stick_height(params) as usize;
+    let band = rows * params.width as usize;
+    let mut work: Vec<Vec<_>> = canvases.iter().map(|_| Vec::new()).collect();
+    for (i, part) in tab.chunks_mut(band.max(1)).enumerate() {
+        let first_row = top + i * rows;
+        work[i % canvases.len()].push((first_row, part));
     }
 
-    let white = Rgb([255, 255, 255]);
-    let black = Rgb([0, 0, 0]);
-    let red =   Rgb([255, 0, 0]);
-    let imgbuf = RgbImage::from_fn(params.width, params.height, |x, y| {
-        match tab[y as usize * tabw + x as usize] {
-            1 => black,
-            2 => red,
-            _ => white,
+    thread::scope(|scope| {
+        for (canvas, parts) in canvases.iter_mut().zip(work) {
+            scope.spawn(move || {
+                for (first_row, part) in parts {
+                    draw_band(groups, params, canvas, first_row, part);
+                }
+            });
         }
     });
+}
 
-    match imgbuf.save(params.path) {
+/* Draws the rows from top into part, with everything that can reach them,
+   clipped. */
+fn draw_band(groups: &[Vec<Stickman>], params: &SmphrParams,
+             canvas: &mut RasterCanvas<Buffer>, top: usize,
+             part: &mut [Rgba<u8>]) {
+    let rows = part.len() / params.width as usize;
+    canvas.pixels_mut().reset(top, rows, params.palette.background);
+
+    draw_rows(groups, params, canvas, top as i32, (top + rows) as i32);
+    part.copy_from_slice(canvas.pixels_mut().pixels());
+}
+
+/* Draws the groups that can reach the rows from top to bottom, excluded. */
+fn draw_rows(groups: &[Vec<Stickman>], params: &SmphrParams,
+             canvas: &mut dyn Canvas, top: i32, bottom: i32) {
+    draw_grid(params, canvas, top, bottom);
+    let reach = reach(params);
+    let (y0, y1) = (top - reach, bottom + reach);
+    let y = |g: &Vec<Stickman>, i: usize| g[i].get_pos().y;
+    // rows of groups come from top to bottom, columns do not
+    let near = if params.layout.writing == Writing::Horizontal {
+        let first = groups.partition_point(|g| y(g, g.len() - 1) < y0);
+        let n = groups[first..].iter().take_while(|g| y(g, 0) < y1).count();
+        &groups[first..first + n]
+    } else {
+        groups
+    };
+    for group in near {
+        let ys = group.iter().map(|s| s.get_pos().y);
+        if ys.clone().min().unwrap() >= y1 || ys.max().unwrap() < y0 {
+            continue;
+        }
+        for s in group.iter() { s.draw(canvas, params); }
+        draw_group(group, canvas, params);
+    }
+}
+
+/* Writes the PNG a few rows of stickmen at a time, one per job, so that
+   only those bands of the image are ever held in memory. */
+fn stream_png(groups: &[Vec<Stickman>], params: &SmphrParams)
+    -> Result<(), ImageError> {
+
+    let out = create(&params.path)?;
+    let mut encoder = png::Encoder::new(out, params.width, params.height);
+    let opaque = params.palette.is_opaque();
+    encoder.set_color(if opaque { png::ColorType::RGB }
+                      else      { png::ColorType::RGBA });
+    encoder.set_depth(png::BitDepth::Eight);
+    let encoding = |e: png::EncodingError| ImageError::IoError(e.into());
+    let mut writer = encoder.write_header().map_err(encoding)?;
+    let ppm = pixels_per_metre(params.dpi).to_be_bytes();
+    let phys = [&ppm[..], &ppm[..], &[1]].concat();
+    writer.write_chunk(*b"pHYs", &phys).map_err(encoding)?;
+    for (kind, data) in text_chunks(params) {
+        writer.write_chunk(kind, &data).map_err(encoding)?;
+    }
+    let mut stream = writer.stream_writer();
+
+    let (tabw, tabh) = (params.width as usize, params.height as usize);
+    let mut canvases = canvases(params);
+    let batch = stick_height(params) as usize * canvases.len();
+    let mut tab = Vec::with_capacity(tabw * batch);
+    let mut line = Vec::with_capacity(tabw * 4);
+    for top in (0..tabh).step_by(batch) {
+        let rows = batch.min(tabh - top);
+        tab.clear();
+        tab.resize(tabw * rows, params.palette.background);
+        draw_bands(groups, params, &mut canvases, top, &mut tab);
+
+        for row in tab.chunks(tabw.max(1)) {
+            line.clear();
+            for p in row {
+                line.extend_from_slice(if opaque { &p.0[..3] } else { &p.0 });
+            }
+            stream.write_all(&line)?;
+        }
+    }
+    stream.finish().map_err(encoding)
+}
+
+/* Resolution as a PNG records it, in pixels per metre. */
+fn pixels_per_metre(dpi: f64) -> u32 {
+    (dpi * 1000.0 / INCH_MM).round() as u32
+}
+
+/* Text and settings the image is drawn from, so that it can be traced back
+   to them. */
+fn text_chunks(params: &SmphrParams) -> Vec<([u8; 4], Vec<u8>)> {
+    let software = format!("smphr {}", env!("CARGO_PKG_VERSION"));
+    let scale = params.scale.to_string();
+    [("Description", params.data.as_str()), ("Software", &software),
+     ("Alphabet", params.alphabet.name()), ("Scale", &scale)].iter()
+        .map(|(k, v)| chunks::text_chunk(k, v))
+        .collect()
+}
+
+/// Prints the text chunks of a PNG file, such as those smphr writes.
+pub fn inspect(path: &str) -> Result<(), SmphrError> {
+    match chunks::read_text(path) {
+        Ok(Some(text)) => {
+            for (keyword, value) in text {
+                println!("{}: {}", keyword, value);
+            }
+            Ok(())
+        },
+        Ok(None) => Err(SmphrError::NotPng(path.to_string())),
+        Err(e) => Err(SmphrError::CouldNotRead(e)),
+    }
+}
+
+/* RGB image, or RGBA when some colour is not opaque. */
+fn to_image(tab: &[Rgba<u8>], params: &SmphrParams) -> DynamicImage {
+    sized_image(tab, params.width, params.height, params)
+}
+
+/* Image of the given size, in the colours of to_image. */
+fn sized_image(tab: &[Rgba<u8>], width: u32, height: u32,
+               params: &SmphrParams) -> DynamicImage {
+    let raw = tab.iter().flat_map(|p| p.0.iter().copied()).collect();
+    let img = ImageBuffer::from_raw(width, height, raw)
+                          .expect("buffer matches the image size");
+    let img = DynamicImage::ImageRgba8(img);
+    if params.palette.is_opaque() {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    } else {
+        img
+    }
+}
+
+pub fn exec(params: SmphrParams) -> Result<(), SmphrError> {
+
+    if let Some(mode) = params.sprites {
+        return atlas::export(&params, mode).map_err(SmphrError::CouldNotWrite);
+    }
+
+    let encoded = params.alphabet.encode(&params.data);
+    let mut signs = Vec::new();
+    for (_, sign) in encoded.iter() {
+        match sign {
+            Ok(s) => signs.push(s.clone()),
+            Err(e) => eprintln!("Error creating stickman, {}.", e),
+        }
+    }
+
+    // first detect invalid inputs:
+    let valid = signs.iter().any(|s| matches!(s, Sign::Pose(_) 
+                                                 | Sign::Strokes(_)));
+    if !valid { return Err(SmphrError::InvalidData); }
+
+    let result = match params.strokes {
+        _ if params.format == Format::Pdf && params.page.poster => {
+            write_poster(&signs, &params).map(warn_cut)
+        },
+        _ if params.format == Format::Pdf => {
+            write_pdf(&signs, &params).map(warn_cut)
+        },
+        StrokeMode::Strip if params.format == Format::Svg => {
+            write_svg(&signs, &params).map(warn_cut)
+        },
+        StrokeMode::Strip if matches!(params.format, Format::Pbm
+                                                     | Format::Ppm) => {
+            let (tab, cut) = render(&signs, &params);
+            warn_cut(cut);
+            write_netpbm(&tab, &params, params.format == Format::Pbm)
+        },
+        StrokeMode::Strip if params.format == Format::Png => {
+            let (groups, n) = layout(&signs, &params);
+            warn_cut(n < signs.len());
+            stream_png(&groups, &params)
+        },
+        StrokeMode::Strip if params.format == Format::Gif => {
+            let (tab, cut) = render(&signs, &params);
+            warn_cut(cut);
+            write_gif(&tab, &params)
+        },
+        StrokeMode::Strip => {
+            let (tab, cut) = render(&signs, &params);
+            warn_cut(cut);
+            to_image(&tab, &params).save(&params.path)
+        },
+        StrokeMode::Animate => {
+            let n = signs.iter().map(|s| s.strokes()).max().unwrap_or(1);
+            let frames: Vec<(Vec<Rgba<u8>>, bool)> = (0..n).map(|k| {
+                let step: Vec<Sign> = signs.iter().map(|s| s.stroke(k))
+                                           .collect();
+                render(&step, &params)
+            }).collect();
+            warn_cut(frames.iter().any(|(_, cut)| *cut));
+            save_animation(&frames, &params)
+        },
+    };
+    match result {
         Ok(_) => {},
         Err(e) => { return Err(SmphrError::CouldNotWrite(e)); }
     }
 
+    if let Some(path) = &params.metadata {
+        metadata::export(path, &params.data, &encoded, &params)
+            .map_err(SmphrError::CouldNotWrite)?;
+    }
+
     Ok(())
 }
+
+/// Draws each text of a batch, going on with the next ones when one cannot
+/// be drawn, and tells whether they all were.
+pub fn exec_batch(jobs: Vec<SmphrParams>) -> bool {
+    let mut all = true;
+    for params in jobs {
+        let path = params.path.clone();
+        if let Err(e) = exec(params) {
+            eprintln!("{}: {}", path, e);
+            all = false;
+        }
+    }
+    all
+}
+
+fn warn_cut(cut: bool) {
+    if cut {
+        eprintln!("Vertical overflow, exiting loop.\
+\nThis will cut the input text.");
+    }
+}
+
+/* Writes a still GIF, also to the standard output, unlike the image crate
+   guessing the format from the path. */
+fn write_gif(tab: &[Rgba<u8>], params: &SmphrParams)
+    -> Result<(), ImageError> {
+    let mut out = create(&params.path)?;
+    to_image(tab, params).write_to(&mut out, ImageOutputFormat::Gif)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn save_animation(frames: &[(Vec<Rgba<u8>>, bool)], params: &SmphrParams)
+    -> Result<(), ImageError> {
+
+    let out = create(&params.path)?;
+    let mut encoder = GifEncoder::new(out);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(frames.iter().map(|(tab, _)| {
+        let img = to_image(tab, params).to_rgba8();
+        Frame::from_parts(img, 0, 0,
+                          Delay::from_numer_denom_ms(FRAME_DELAY_MS, 1))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /* Pixels drawn by the groups on a blank image of the parameters. */
+    fn draw(groups: &[Vec<Stickman>], params: &SmphrParams) -> Vec<bool> {
+        let background = params.palette.background;
+        let (w, h) = (params.width as usize, params.height as usize);
+        let buffer = Buffer::new(w, h, background);
+        let mut canvas = RasterCanvas::new(buffer, false);
+        draw_rows(groups, params, &mut canvas, 0, params.height as i32);
+        canvas.into_pixels().pixels().iter().map(|p| *p != background)
+            .collect()
+    }
+
+    #[test]
+    fn group_marks_stay_in_their_row() {
+        let mut params = SmphrParams::default();
+        params.alphabet = Alphabet::builtin("ja").unwrap();
+        let signs: Vec<Sign> = params.alphabet.encode("ガガガガガガガガ")
+            .into_iter().filter_map(|(_, s)| s.ok()).collect();
+        for (writing, rtl) in [(Writing::Horizontal, false),
+                               (Writing::Vertical, false),
+                               (Writing::Vertical, true)] {
+            params.layout.writing = writing;
+            params.layout.rtl = rtl;
+            let (placed, _) = place(&signs, &params);
+            let (first, second): (Vec<_>, Vec<_>) = placed.into_iter()
+                .partition(|p| p.row == 0);
+            assert!(!first.is_empty() && !second.is_empty());
+            assert!(first.iter().all(|p| p.figures.len() > 1));
+
+            let figures = |v: Vec<Placed>| -> Vec<Vec<Stickman>> {
+                v.into_iter().map(|p| p.figures).collect()
+            };
+            let a = draw(&figures(first), &params);
+            let b = draw(&figures(second), &params);
+            assert!(!a.iter().zip(b.iter()).any(|(a, b)| *a && *b));
+        }
+    }
+
+    #[test]
+    fn signaller_view_mirrors_observer_view() {
+        let mut params = SmphrParams::default();
+        // one figure, centred on the middle column of the image
+        params.width = stick_width(&params) as u32;
+        params.height = stick_height(&params) as u32;
+        let width = params.width as usize;
+        let letters = "ADGKRVZ".chars();
+        for (c, antialias) in letters.flat_map(|c| [(c, false), (c, true)]) {
+            params.antialias = antialias;
+            let signs: Vec<Sign> = params.alphabet.encode(&c.to_string())
+                .into_iter().filter_map(|(_, s)| s.ok()).collect();
+            params.view = Viewpoint::Observer;
+            let (observer, _) = render(&signs, &params);
+            params.view = Viewpoint::Signaller;
+            let (signaller, _) = render(&signs, &params);
+            for (a, b) in observer.chunks(width).zip(signaller.chunks(width)) {
+                assert!(a.iter().eq(b.iter().rev()), "{} is not mirrored", c);
+            }
+        }
+    }
+
+    #[test]
+    fn transparent_background_is_kept() {
+        let mut params = SmphrParams::default();
+        params.palette.background = parse_color("transparent").ok().unwrap();
+        params.path = std::env::temp_dir()
+            .join(format!("smphr-alpha-{}.png", std::process::id()))
+            .to_string_lossy().into_owned();
+        let signs: Vec<Sign> = params.alphabet.encode("SEE THROUGH")
+            .into_iter().filter_map(|(_, s)| s.ok()).collect();
+        let (groups, _) = layout(&signs, &params);
+        stream_png(&groups, &params).unwrap();
+
+        let file = File::open(&params.path).unwrap();
+        let (info, mut reader) = png::Decoder::new(file).read_info().unwrap();
+        let mut streamed = vec![0; info.buffer_size()];
+        reader.next_frame(&mut streamed).unwrap();
+        fs::remove_file(&params.path).unwrap();
+
+        assert_eq!(info.color_type, png::ColorType::RGBA);
+        let (tab, _) = render(&signs, &params);
+        let alphas: Vec<u8> = streamed.chunks(4).map(|p| p[3]).collect();
+        assert!(tab.iter().zip(alphas.iter())
+                .all(|(p, &a)| (*p == params.palette.background) == (a == 0)));
+        assert!(alphas.contains(&255));
+    }
+
+    #[test]
+    fn parses_lengths() {
+        assert_eq!(parse_length("300px", "pt", 96.0), Some(300.0));
+        assert_eq!(parse_length("1in", "px", 96.0), Some(96.0));
+        assert_eq!(parse_length(" 2.54 cm", "px", 100.0), Some(100.0));
+        assert_eq!(parse_length("72", "pt", 96.0), Some(96.0));
+        assert_eq!(parse_length("12", "px", 96.0), Some(12.0));
+        assert_eq!(parse_length("12furlongs", "px", 96.0), None);
+        assert_eq!(parse_length("cm", "px", 96.0), None);
+    }
+
+    #[test]
+    fn parses_pages() {
+        let page = |s| parse_page(s, 96.0).ok().unwrap();
+        let near = |a: (f64, f64), b: (f64, f64)| {
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+        };
+        let cm = 72.0 / 2.54;
+        assert_eq!(page("A4"), (595.276, 841.89));
+        assert!(near(page("100x200"), (100.0, 200.0)));
+        assert!(near(page("100pxx200px"), (75.0, 150.0)));
+        assert!(near(page("20cmx20cm"), (20.0 * cm, 20.0 * cm)));
+        assert!(near(page("1inx15cm"), (72.0, 15.0 * cm)));
+        for bad in ["a6", "100", "100x", "x100", "0x100", "100pxx", "1x1yd"] {
+            assert!(parse_page(bad, 96.0).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn tiles_a_poster() {
+        let mut params = SmphrParams::default();
+        params.dpi = INCH_PT;
+        params.page = Page { width: 200.0, height: 300.0, margin: 20.0,
+                             overlap: 10.0, ..Page::default() };
+        // tiles of 160 by 260 pixels, each starting 10 before the last ends
+        params.width = 400;
+        params.height = 300;
+        let (size, pages) = tiles(&params);
+        assert_eq!(size, (3, 2));
+        assert_eq!(pages, [(0.0, 0.0, 160.0, 260.0),
+                           (150.0, 0.0, 160.0, 260.0),
+                           (300.0, 0.0, 100.0, 260.0),
+                           (0.0, 250.0, 160.0, 50.0),
+                           (150.0, 250.0, 160.0, 50.0),
+                           (300.0, 250.0, 100.0, 50.0)]);
+        // the overlap alone does not call for a page of its own
+        params.width = 310;
+        params.height = 260;
+        assert_eq!(tiles(&params), ((2, 1), vec![(0.0, 0.0, 160.0, 260.0),
+                                                 (150.0, 0.0, 160.0, 260.0)]));
+        params.width = 160;
+        assert_eq!(tiles(&params).0, (1, 1));
+    }
+
+    #[test]
+    fn marks_where_the_next_pages_start() {
+        let page = Page { width: 200.0, height: 300.0, margin: 20.0,
+                          overlap: 10.0, ..Page::default() };
+        let lines = |place| mark_lines(&page, place, (3, 2));
+        let at_x = |place, x| lines(place).iter()
+            .filter(|(a, b)| a.0 == x && b.0 == x).count();
+        let at_y = |place, y| lines(place).iter()
+            .filter(|(a, b)| a.1 == y && b.1 == y).count();
+        // 8 cut marks, then 2 marks on each side followed by another page
+        assert_eq!(lines((0, 0)).len(), 12);
+        assert_eq!(at_x((0, 0), 170.0), 2);
+        assert_eq!(at_y((0, 0), 270.0), 2);
+        assert_eq!(at_x((2, 0), 170.0), 0);
+        assert_eq!(at_y((1, 1), 270.0), 0);
+        assert_eq!(lines((2, 1)).len(), 8);
+    }
+
+    #[test]
+    fn keeps_the_poster_map_on_the_page() {
+        let mut page = Page { width: 200.0, height: 300.0, margin: 0.0,
+                              ..Page::default() };
+        let (cell, (x, y)) = poster_map(&page, (3, 2));
+        assert!(cell >= 1.0 && x >= 0.0 && y >= 0.0);
+        page.margin = 20.0;
+        let (cell, (x, _)) = poster_map(&page, (500, 2));
+        assert!(cell > 0.0 && x >= 0.0 && x + 500.0 * cell <= page.width);
+    }
+
+    #[test]
+    fn reads_texts_without_their_last_line_break() {
+        let dir = std::env::temp_dir()
+            .join(format!("smphr-input-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let read = |content: &str| {
+            let path = dir.join("text.txt");
+            fs::write(&path, content).unwrap();
+            read_input(&path.to_string_lossy()).ok().unwrap()
+        };
+        assert_eq!(read("hello\n"), "hello");
+        assert_eq!(read("hello"), "hello");
+        assert_eq!(read("hello\r\nworld\r\n"), "hello\nworld");
+        assert_eq!(read("hello\n\n"), "hello\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_batches() {
+        let dir = std::env::temp_dir()
+            .join(format!("smphr-batch-{}", std::process::id()));
+        let texts = dir.join("texts");
+        fs::create_dir_all(&texts).unwrap();
+        let batch = |path: &Path| read_batch(&path.to_string_lossy()).ok()
+            .unwrap();
+
+        // lines are numbered as in the file, blank ones left out
+        let lines = dir.join("lines.txt");
+        fs::write(&lines, "one\r\n\n  \nfour\n").unwrap();
+        assert_eq!(batch(&lines), [("1".to_string(), "one".to_string()),
+                                   ("4".to_string(), "four".to_string())]);
+
+        // files by order of name, hidden ones and directories left out
+        fs::write(texts.join("b.txt"), "bee\n").unwrap();
+        fs::write(texts.join("a.txt"), "ay").unwrap();
+        fs::write(texts.join(".hidden"), "no").unwrap();
+        fs::create_dir_all(texts.join("sub")).unwrap();
+        assert_eq!(batch(&texts), [("a".to_string(), "ay".to_string()),
+                                   ("b".to_string(), "bee".to_string())]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fills_output_templates() {
+        let fields = [("n", "3"), ("name", "note")];
+        assert_eq!(fill_template("out/{name}-{n}.png", &fields),
+                   "out/note-3.png");
+        assert_eq!(fill_template("{n}{n}.svg", &fields), "33.svg");
+        assert_eq!(fill_template("{page}.pdf", &fields), "{page}.pdf");
+    }
+
+    #[test]
+    fn records_the_resolution_in_pixels_per_metre() {
+        assert_eq!(pixels_per_metre(96.0), 3780);
+        assert_eq!(pixels_per_metre(300.0), 11811);
+        assert_eq!(pixels_per_metre(25.4), 1000);
+    }
+
+    #[test]
+    fn threads_draw_the_same_pixels() {
+        let mut params = SmphrParams::default();
+        params.antialias = true;
+        let signs: Vec<Sign> = params.alphabet.encode("DRAWN BY THREADS")
+            .into_iter().filter_map(|(_, s)| s.ok()).collect();
+        params.jobs = 1;
+        let (alone, _) = render(&signs, &params);
+        for jobs in [2, 3, 7] {
+            params.jobs = jobs;
+            assert!(render(&signs, &params).0 == alone);
+        }
+    }
+
+    #[test]
+    fn streamed_png_matches_render() {
+        let mut params = SmphrParams::default();
+        params.jobs = 2;
+        params.path = std::env::temp_dir()
+            .join(format!("smphr-stream-{}.png", std::process::id()))
+            .to_string_lossy().into_owned();
+        let signs: Vec<Sign> = params.alphabet.encode("STREAMED IN BANDS")
+            .into_iter().filter_map(|(_, s)| s.ok()).collect();
+        let (groups, _) = layout(&signs, &params);
+        stream_png(&groups, &params).unwrap();
+
+        let file = File::open(&params.path).unwrap();
+        let (info, mut reader) = png::Decoder::new(file).read_info().unwrap();
+        let mut streamed = vec![0; info.buffer_size()];
+        reader.next_frame(&mut streamed).unwrap();
+        fs::remove_file(&params.path).unwrap();
+
+        let (tab, _) = render(&signs, &params);
+        let rendered: Vec<u8> = tab.iter().flat_map(|p| p.0[..3].to_vec())
+            .collect();
+        assert_eq!(info.color_type, png::ColorType::RGB);
+        assert!(streamed == rendered);
+    }
+}
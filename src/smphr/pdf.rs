@@ -0,0 +1,317 @@
+use super::canvas::Canvas;
+use super::stroke::Contour;
+use image::Rgba;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/* Bezier control distance approximating a quarter circle of radius 1. */
+static KAPPA: f64 = 0.552_284_749_8;
+/* Box of the figures drawn as form XObjects, large enough for any. */
+static FIGURE_BOX: f64 = 10000.0;
+
+/* Widths of the Helvetica characters from space to tilde, in thousandths
+   of the font size. */
+static HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333,
+    278, 278, 556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278,
+    584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278,
+    500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944,
+    667, 667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556,
+    278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500,
+    278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// PDF document drawn page by page. Coordinates are in points from the top
+/// left corner of the page, y going down as in the images.
+pub struct Pdf {
+    width: f64,
+    height: f64,
+    pages: Vec<String>,
+    ops: String,
+    /* form XObject of each figure key */
+    figures: BTreeMap<u32, String>,
+    /* opacities used, each with its graphics state */
+    alphas: BTreeSet<u8>,
+}
+
+impl Pdf {
+    /// Document whose pages are width by height points.
+    pub fn new(width: f64, height: f64) -> Pdf {
+        Pdf {
+            width,
+            height,
+            pages: Vec::new(),
+            ops: String::new(),
+            figures: BTreeMap::new(),
+            alphas: BTreeSet::new(),
+        }
+    }
+
+    /// Ends the current page, if any, and starts a new one painted with the
+    /// background colour.
+    pub fn new_page(&mut self, background: Rgba<u8>) {
+        if !self.ops.is_empty() {
+            self.pages.push(std::mem::take(&mut self.ops));
+        }
+        // y going down from the top of the page
+        self.ops.push_str(&format!("1 0 0 -1 0 {} cm\n", num(self.height)));
+        let (w, h) = (self.width, self.height);
+        self.paint(background, |ops| {
+            format!("{}0 0 {} {} re f", ops, num(w), num(h))
+        });
+    }
+
+    /// Moves the origin to (x, y) and multiplies the lengths by scale until
+    /// the matching restore.
+    pub fn save(&mut self, x: f64, y: f64, scale: f64) {
+        let k = num(scale);
+        let _ = writeln!(self.ops, "q {} 0 0 {} {} {} cm", k, k, num(x), num(y));
+    }
+
+    pub fn restore(&mut self) {
+        self.ops.push_str("Q\n");
+    }
+
+    /// Leaves out of the drawing whatever lies out of the rectangle, until
+    /// the restore matching the last save.
+    pub fn clip(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let _ = writeln!(self.ops, "{} {} {} {} re W n", num(x), num(y),
+                         num(width), num(height));
+    }
+
+    /// Writes a line of Helvetica of the given size from x, its baseline at
+    /// y. Characters out of Latin-1 are replaced with question marks.
+    pub fn text(&mut self, x: f64, y: f64, size: f64, text: &str,
+                color: Rgba<u8>) {
+        let s = escape(text);
+        // text is written upright, undoing the flip of the page
+        self.paint(color, |fill| {
+            format!("q {}1 0 0 -1 {} {} cm BT /F1 {} Tf 0 0 Td ({}) Tj ET Q",
+                    fill, num(x), num(y), num(size), s)
+        });
+    }
+
+    /// Writes the document with all its pages.
+    pub fn write<W: Write>(mut self, out: &mut W) -> io::Result<()> {
+        if !self.ops.is_empty() {
+            self.pages.push(std::mem::take(&mut self.ops));
+        }
+        let mut objects: Vec<String> = Vec::new();
+        let figures = 5;
+        let first_page = figures + self.figures.len();
+        let kids: Vec<String> = (0..self.pages.len())
+            .map(|i| format!("{} 0 R", first_page + 2 * i)).collect();
+
+        objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+        objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>",
+                             kids.join(" "), self.pages.len()));
+        objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica \
+/Encoding /WinAnsiEncoding >>".to_string());
+        let states: Vec<String> = self.alphas.iter().map(|a| {
+            let alpha = num(*a as f64 / 255.0);
+            format!("/A{} << /ca {} /CA {} >>", a, alpha, alpha)
+        }).collect();
+        let xobjects: Vec<String> = self.figures.keys().enumerate()
+            .map(|(i, k)| format!("/Fig{} {} 0 R", k, figures + i)).collect();
+        objects.push(format!("<< /Font << /F1 3 0 R >> /ExtGState << {} >> \
+/XObject << {} >> >>", states.join(" "), xobjects.join(" ")));
+        for ops in self.figures.values() {
+            let b = num(FIGURE_BOX);
+            objects.push(stream(&format!("/Type /XObject /Subtype /Form \
+/BBox [-{} -{} {} {}] /Resources 4 0 R", b, b, b, b), ops));
+        }
+        for (i, ops) in self.pages.iter().enumerate() {
+            objects.push(format!("<< /Type /Page /Parent 2 0 R \
+/MediaBox [0 0 {} {}] /Resources 4 0 R /Contents {} 0 R >>",
+                                 num(self.width), num(self.height),
+                                 first_page + 2 * i + 1));
+            objects.push(stream("", ops));
+        }
+
+        let mut doc = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, obj) in objects.iter().enumerate() {
+            offsets.push(doc.len());
+            let _ = write!(doc, "{} 0 obj\n{}\nendobj\n", i + 1, obj);
+        }
+        let xref = doc.len();
+        let _ = write!(doc, "xref\n0 {}\n0000000000 65535 f \n",
+                       objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(doc, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(doc, "trailer\n<< /Size {} /Root 1 0 R >>\n\
+startxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+        out.write_all(doc.as_bytes())
+    }
+
+    /* Appends the operators f makes out of the colour operators, within
+       the opacity of the colour. Nothing is drawn in a transparent colour. */
+    fn paint<F>(&mut self, color: Rgba<u8>, f: F) where F: FnOnce(&str) -> String {
+        if color[3] == 0 { return; }
+        let [r, g, b, a] = color.0;
+        let (r, g, b) = (num(r as f64 / 255.0), num(g as f64 / 255.0),
+                         num(b as f64 / 255.0));
+        let set = format!("{} {} {} rg {} {} {} RG ", r, g, b, r, g, b);
+        if a == 255 {
+            let _ = writeln!(self.ops, "{}", f(&set));
+        } else {
+            self.alphas.insert(a);
+            let _ = writeln!(self.ops, "q /A{} gs {} Q", a, f(&set));
+        }
+    }
+}
+
+impl Canvas for Pdf {
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64,
+            color: Rgba<u8>) {
+        let width = num(width.max(1.0));
+        self.paint(color, |set| {
+            format!("{}{} w 2 J {} {} m {} {} l S", set, width, num(from.0),
+                    num(from.1), num(to.0), num(to.1))
+        });
+    }
+
+    fn circle(&mut self, centre: (f64, f64), r: f64, thickness: f64,
+              color: Rgba<u8>) {
+        // the same ring as the raster one, from r - 1/2 to r + t - 1/2
+        let t = thickness.max(1.0);
+        let r = r + (t - 1.0) / 2.0;
+        let (x, y) = centre;
+        let k = r * KAPPA;
+        let path = format!("{} {} m {} {} {} {} {} {} c {} {} {} {} {} {} c \
+{} {} {} {} {} {} c {} {} {} {} {} {} c",
+            num(x + r), num(y),
+            num(x + r), num(y + k), num(x + k), num(y + r), num(x), num(y + r),
+            num(x - k), num(y + r), num(x - r), num(y + k), num(x - r), num(y),
+            num(x - r), num(y - k), num(x - k), num(y - r), num(x), num(y - r),
+            num(x + k), num(y - r), num(x + r), num(y - k), num(x + r), num(y));
+        self.paint(color, |set| {
+            format!("{}{} w {} S", set, num(t), path)
+        });
+    }
+
+    fn polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>) {
+        if points.is_empty() { return; }
+        // edges included, as on the images
+        let path = path(&[points.to_vec()]);
+        self.paint(color, |set| format!("{}1 w 1 j {} b", set, path));
+    }
+
+    fn fill(&mut self, contours: &[Contour], color: Rgba<u8>) {
+        let path = path(contours);
+        self.paint(color, |set| format!("{}{} f", set, path));
+    }
+
+    fn figure(&mut self, key: u32, x: i32, y: i32, _reach: i32,
+              draw: &dyn Fn(&mut dyn Canvas)) {
+        if !self.figures.contains_key(&key) {
+            let page = std::mem::take(&mut self.ops);
+            draw(self);
+            let ops = std::mem::replace(&mut self.ops, page);
+            self.figures.insert(key, ops);
+        }
+        let _ = writeln!(self.ops, "q 1 0 0 1 {} {} cm /Fig{} Do Q", x, y, key);
+    }
+}
+
+/* Path made of the closed contours. */
+fn path(contours: &[Contour]) -> String {
+    let mut path = String::new();
+    for c in contours.iter().filter(|c| !c.is_empty()) {
+        let _ = write!(path, "{} {} m ", num(c[0].0), num(c[0].1));
+        for p in &c[1..] {
+            let _ = write!(path, "{} {} l ", num(p.0), num(p.1));
+        }
+        path.push_str("h ");
+    }
+    path
+}
+
+/* Stream object with the entries of its dictionary. */
+fn stream(entries: &str, ops: &str) -> String {
+    let ops = ops.trim_end();
+    format!("<< {} /Length {} >>\nstream\n{}\nendstream",
+            entries, ops.len(), ops)
+}
+
+/// Short decimal writing of a number.
+pub fn num(v: f64) -> String {
+    let s = format!("{:.3}", v);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" { "0".to_string() } else { s.to_string() }
+}
+
+/// Width of a line of Helvetica, in font sizes.
+pub fn text_width(text: &str) -> f64 {
+    text.chars().map(|c| match c {
+        ' '..='~' => HELVETICA[c as usize - 32] as f64,
+        _ => 556.0,
+    }).sum::<f64>() / 1000.0
+}
+
+/// Whether the text can be written in Helvetica, without a font of its own:
+/// it must only hold Latin-1 characters.
+pub fn can_write(text: &str) -> bool {
+    text.chars().all(|c| matches!(c, ' '..='~' | '\u{a0}'..='\u{ff}'))
+}
+
+/* PDF string of a text in WinAnsi encoding, which matches Latin-1 for the
+   letters. */
+fn escape(text: &str) -> String {
+    let mut s = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => { s.push('\\'); s.push(c); },
+            ' '..='~' => s.push(c),
+            '\u{a0}'..='\u{ff}' => { let _ = write!(s, "\\{:03o}", c as u32); },
+            _ => s.push('?'),
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cross_references_point_at_objects() {
+        let mut pdf = Pdf::new(200.0, 100.0);
+        for page in 0..2 {
+            pdf.new_page(Rgba([255, 255, 255, 255]));
+            pdf.text(100.0, 20.0, 10.0, "Page (1) é", Rgba([0, 0, 0, 255]));
+            pdf.figure(page, 50, 50, 20, &|c: &mut dyn Canvas| {
+                c.line((0.0, 0.0), (10.0, 5.0), 1.0, Rgba([255, 0, 0, 128]));
+            });
+        }
+        let mut out = Vec::new();
+        pdf.write(&mut out).unwrap();
+        let doc = String::from_utf8(out).unwrap();
+
+        assert!(doc.starts_with("%PDF-1.4\n") && doc.ends_with("%%EOF\n"));
+        assert!(doc.contains("(Page \\(1\\) \\351) Tj"));
+        assert!(doc.contains("/Count 2"));
+        let xref = doc.rfind("startxref\n").unwrap();
+        let start: usize = doc[xref + 10..].lines().next().unwrap()
+                                           .parse().unwrap();
+        let entries = doc[start..].lines().skip(3);
+        for (i, entry) in entries.take_while(|l| l.ends_with(" n ")).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(doc[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+        for part in doc.split("/Length ").skip(1) {
+            let (length, rest) = part.split_once(" >>\nstream\n").unwrap();
+            let length: usize = length.parse().unwrap();
+            assert!(rest[length..].starts_with("\nendstream"));
+        }
+    }
+
+    #[test]
+    fn writes_latin_1_only() {
+        assert!(can_write("Page {page} of {pages}, (é)"));
+        assert!(!can_write("Страница {page}"));
+        assert!(!can_write("€"));
+    }
+}
@@ -0,0 +1,652 @@
+use super::canvas::Canvas;
+use super::sprite::{Recorder, Sprite};
+use super::stroke::{stroke, Cap, Contour, Join};
+use image::Rgba;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// Pixels the primitives draw on. Coordinates are signed: the primitives
+/// clip their shapes to the bounds before writing.
+pub trait Pixels {
+    /// Area that can be written, as (x0, y0, x1, y1) with x1 and y1
+    /// excluded.
+    fn bounds(&self) -> (i32, i32, i32, i32);
+
+    /// Sets pixel (x, y), which lies in the bounds.
+    fn set(&mut self, x: i32, y: i32, color: Rgba<u8>);
+
+    /// Blends color over pixel (x, y), which lies in the bounds.
+    fn blend(&mut self, x: i32, y: i32, color: Rgba<u8>, coverage: f64);
+}
+
+/// Pixels of an image, or of a band of its rows starting at top, row after
+/// row.
+pub struct Buffer {
+    pixels: Vec<Rgba<u8>>,
+    width: usize,
+    height: usize,
+    top: usize,
+}
+
+impl Buffer {
+    pub fn new(width: usize, height: usize, background: Rgba<u8>) -> Buffer {
+        Buffer {
+            pixels: vec![background; width * height],
+            width,
+            height,
+            top: 0,
+        }
+    }
+
+    /// Moves the buffer to the band of rows from top, cleared.
+    pub fn reset(&mut self, top: usize, height: usize, background: Rgba<u8>) {
+        self.pixels.clear();
+        self.pixels.resize(self.width * height, background);
+        self.height = height;
+        self.top = top;
+    }
+
+    pub fn pixels(&self) -> &[Rgba<u8>] {
+        &self.pixels
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y as usize - self.top) * self.width + x as usize
+    }
+}
+
+impl Pixels for Buffer {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (0, self.top as i32, self.width as i32, (self.top + self.height) as i32)
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: Rgba<u8>) {
+        let k = self.index(x, y);
+        self.pixels[k] = color;
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: Rgba<u8>, coverage: f64) {
+        let k = self.index(x, y);
+        self.pixels[k] = blend(self.pixels[k], color, coverage);
+    }
+}
+
+/* Pixels seen from an origin moved to (dx, dy). */
+struct Shifted<'a, P: Pixels + ?Sized> {
+    pixels: &'a mut P,
+    dx: i32,
+    dy: i32,
+}
+
+impl<P: Pixels + ?Sized> Pixels for Shifted<'_, P> {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        let (x0, y0, x1, y1) = self.pixels.bounds();
+        (x0 - self.dx, y0 - self.dy, x1 - self.dx, y1 - self.dy)
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: Rgba<u8>) {
+        self.pixels.set(x + self.dx, y + self.dy, color);
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: Rgba<u8>, coverage: f64) {
+        self.pixels.blend(x + self.dx, y + self.dy, color, coverage);
+    }
+}
+
+/// Canvas drawing on pixels, aliased or anti-aliased. Each figure is drawn
+/// once, then copied wherever it appears again.
+pub struct RasterCanvas<P: Pixels> {
+    pixels: P,
+    antialias: bool,
+    origin: (i32, i32),
+    sprites: HashMap<u32, Sprite>,
+}
+
+impl<P: Pixels> RasterCanvas<P> {
+    pub fn new(pixels: P, antialias: bool) -> RasterCanvas<P> {
+        RasterCanvas {
+            pixels,
+            antialias,
+            origin: (0, 0),
+            sprites: HashMap::new(),
+        }
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut P {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> P {
+        self.pixels
+    }
+
+    fn shifted(&mut self) -> Shifted<'_, P> {
+        Shifted { pixels: &mut self.pixels, dx: self.origin.0, dy: self.origin.1 }
+    }
+
+    /* Draws a figure without going through its sprite. */
+    fn draw_at(&mut self, x: i32, y: i32, draw: &dyn Fn(&mut dyn Canvas)) {
+        let origin = self.origin;
+        self.origin = (origin.0 + x, origin.1 + y);
+        draw(self);
+        self.origin = origin;
+    }
+}
+
+/* Nearest pixel of a point. */
+fn pixel(p: (f64, f64)) -> (i32, i32) {
+    (p.0.round() as i32, p.1.round() as i32)
+}
+
+impl<P: Pixels> Canvas for RasterCanvas<P> {
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64,
+            color: Rgba<u8>) {
+        // square caps cover the ends as the thin lines do
+        if width.round() > 1.0 {
+            let outline = stroke(&[from, to], width, Cap::Square, Join::Round);
+            return self.fill(&outline, color);
+        }
+        let antialias = self.antialias;
+        let px = &mut self.shifted();
+        // blended lines keep their ends between pixels
+        if antialias {
+            draw_line2_aa(from, to, color, px);
+        } else {
+            let ((xi, yi), (xf, yf)) = (pixel(from), pixel(to));
+            draw_line2(xi, yi, xf, yf, color, px);
+        }
+    }
+
+    fn circle(&mut self, centre: (f64, f64), r: f64, thickness: f64,
+              color: Rgba<u8>) {
+        let (xc, yc) = pixel(centre);
+        let r = r.round().max(0.0) as usize;
+        let t = thickness.round().max(1.0) as usize;
+        let antialias = self.antialias;
+        let px = &mut self.shifted();
+        if antialias {
+            draw_circle_aa(xc, yc, r, t, color, px);
+        } else {
+            draw_circle(xc, yc, r, t, color, px);
+        }
+    }
+
+    fn polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>) {
+        let antialias = self.antialias;
+        let px = &mut self.shifted();
+        if antialias {
+            fill_polygon_aa(points, color, px);
+        } else {
+            let points: Vec<(i32, i32)> = points.iter().map(|p| pixel(*p))
+                                                .collect();
+            fill_polygon(&points, color, px);
+        }
+    }
+
+    fn fill(&mut self, contours: &[Contour], color: Rgba<u8>) {
+        let antialias = self.antialias;
+        let px = &mut self.shifted();
+        if antialias {
+            fill_contours_aa(contours, color, px);
+        } else {
+            fill_contours(contours, color, px);
+        }
+    }
+
+    fn figure(&mut self, key: u32, x: i32, y: i32, reach: i32,
+              draw: &dyn Fn(&mut dyn Canvas)) {
+        // a sprite larger than the pixels costs more than drawing again
+        let (x0, y0, x1, y1) = self.pixels.bounds();
+        let side = 2 * (reach as i64 + 1) + 1;
+        if side * side > (x1 - x0) as i64 * (y1 - y0) as i64 {
+            return self.draw_at(x, y, draw);
+        }
+        if !self.sprites.contains_key(&key) {
+            let mut recorder = RasterCanvas::new(Recorder::new(reach + 1),
+                                                 self.antialias);
+            recorder.draw_at(0, 0, draw);
+            self.sprites.insert(key, recorder.into_pixels().into_sprite());
+        }
+        let (x, y) = (self.origin.0 + x, self.origin.1 + y);
+        self.sprites[&key].paste(&mut self.pixels, x, y);
+    }
+}
+
+/* Sets pixel (x, y) when it lies in the bounds. */
+fn plot<P: Pixels + ?Sized>(x: i32, y: i32, color: Rgba<u8>, px: &mut P) {
+    let (x0, y0, x1, y1) = px.bounds();
+    if x < x0 || y < y0 || x >= x1 || y >= y1 { return; }
+    px.set(x, y, color);
+}
+
+/* Whether the box (x1, y1) -- (x2, y2) lies entirely out of the bounds. */
+fn outside<P: Pixels + ?Sized>(x1: i32, y1: i32, x2: i32, y2: i32, px: &P)
+    -> bool {
+    let (bx0, by0, bx1, by1) = px.bounds();
+    x2 < bx0 || y2 < by0 || x1 >= bx1 || y1 >= by1
+}
+
+/** Draws the line (xi, yi) -- (xf, yf) on px. */
+pub fn draw_line2<P: Pixels + ?Sized>(xi: i32, yi: i32, xf: i32, yf: i32,
+                                      color: Rgba<u8>, px: &mut P) {
+
+    let (x1, x2, x_inversed) = if xf < xi { (xf, xi, true)   } 
+                               else       { (xi, xf, false)  };
+    let (y1, y2, y_inversed) = if yf < yi { (yf, yi, true)   }
+                               else       { (yi, yf, false)  };
+    if outside(x1, y1, x2, y2, px) { return; }
+    let mut plot = |x: i32, y: i32| plot(x, y, color, px);
+
+    if x1 == x2 {
+        for i in y1..=y2 {   plot(x1, i);   }
+        return
+    }
+    if y1 == y2 {
+        for i in x1..=x2 {   plot(i, y1);   }
+        return
+    }
+
+    let mut y = y1;
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let mut e: i32 = -dx;
+    let (ex, ey) = (2*dy, -2*dx);
+
+    // the line is drawn in quadrant 4, then mirrored into its own quadrant
+    let at = |x: i32, y: i32| {
+        (if x_inversed { x1 + x2 - x } else { x },
+         if y_inversed { y1 + y2 - y } else { y })
+    };
+    for x in x1..x2 {
+        let (px, py) = at(x, y);
+        plot(px, py);
+
+        e += ex;
+        while e >= 0 {
+            y += 1;
+            e += ey;
+            if e >= 0 {
+                let (px, py) = at(x, y);
+                plot(px, py);
+            }
+        }
+    }
+}
+
+/// Fills the polygon, edges included: the pixels lying inside following the
+/// non-zero winding rule are set, then its outline is drawn so that thin or
+/// degenerate polygons still show.
+pub fn fill_polygon<P: Pixels + ?Sized>(points: &[(i32, i32)],
+                                        color: Rgba<u8>, px: &mut P) {
+    if points.is_empty() { return; }
+    let pts: Contour = points.iter().map(|(x, y)| (*x as f64, *y as f64))
+                             .collect();
+    fill_contours(&[pts], color, px);
+
+    for i in 0..points.len() {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        draw_line2(a.0, a.1, b.0, b.1, color, px);
+    }
+}
+
+/// Fills the contours with the non-zero winding rule, setting the pixels
+/// whose centre lies inside or on the left or right edge, so that mirrored
+/// shapes cover mirrored pixels.
+pub fn fill_contours<P: Pixels + ?Sized>(contours: &[Contour],
+                                         color: Rgba<u8>, px: &mut P) {
+    let (bx0, by0, bx1, by1) = px.bounds();
+    let (y0, y1) = match rows(contours, by0, by1) {
+        Some(r) => r,
+        None => return,
+    };
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for y in y0..y1 {
+        scan_spans(contours, y as f64, &mut crossings, |xa, xb| {
+            let first = xa.ceil().max(bx0 as f64) as i32;
+            let last = (xb.floor() + 1.0).min(bx1 as f64) as i32;
+            for x in first..last {
+                px.set(x, y, color);
+            }
+        });
+    }
+}
+
+/* Range of rows between y0 and y1 a shape can cover, if it is not entirely
+   above or below. */
+fn rows(contours: &[Contour], y0: i32, y1: i32) -> Option<(i32, i32)> {
+    let ys = contours.iter().flat_map(|c| c.iter().map(|p| p.1));
+    let (ymin, ymax) = ys.fold((f64::MAX, f64::MIN),
+                               |(lo, hi), y| (lo.min(y), hi.max(y)));
+    if ymax < y0 as f64 || ymin >= y1 as f64 { return None; }
+    Some((ymin.max(y0 as f64).floor() as i32,
+          (ymax.floor() as i32 + 1).min(y1)))
+}
+
+/* Calls f on each span [xa, xb) of the scanline at height y lying inside
+   the contours, following the non-zero winding rule. */
+fn scan_spans<F>(contours: &[Contour], y: f64, crossings: &mut Vec<(f64, i32)>,
+                 mut f: F) where F: FnMut(f64, f64) {
+    crossings.clear();
+    for pts in contours {
+        for i in 0..pts.len() {
+            let (a, b) = (pts[i], pts[(i + 1) % pts.len()]);
+            if (a.1 <= y) == (b.1 <= y) { continue; }
+            let x = a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1);
+            crossings.push((x, if b.1 > a.1 { 1 } else { -1 }));
+        }
+    }
+    crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let (mut winding, mut start) = (0, 0.0);
+    for (x, dir) in crossings.iter() {
+        let before = winding;
+        winding += dir;
+        if before == 0 && winding != 0 {
+            start = *x;
+        } else if before != 0 && winding == 0 {
+            f(start, *x);
+        }
+    }
+}
+
+pub fn draw_circle<P: Pixels + ?Sized>(xc: i32, yc: i32, r: usize, t: usize,
+                                       color: Rgba<u8>, px: &mut P) {
+    let reach = (r + t) as i32;
+    if outside(xc - reach, yc - reach, xc + reach, yc + reach, px) {
+        return;
+    }
+
+    // rings wholly out of the bounds are left out, as thick ones can be
+    let (x0, y0, x1, y1) = px.bounds();
+    let far = |c: i32, a: i32, b: i32| (c - a).abs().max((c - b).abs()) as f64;
+    let near = |c: i32, a: i32, b: i32| (a - c).max(c - b).max(0) as f64;
+    let farthest = far(xc, x0, x1 - 1).hypot(far(yc, y0, y1 - 1)) + 1.0;
+    let nearest = near(xc, x0, x1 - 1).hypot(near(yc, y0, y1 - 1)) - 1.0;
+    for i in 0..t {
+        let ring = (r + i) as f64;
+        if ring > farthest { break; }
+        if ring < nearest { continue; }
+        draw_circle2(xc, yc, r + i, color, px);
+    }
+}
+fn draw_circle2<P: Pixels + ?Sized>(xc: i32, yc: i32, r: usize,
+                                    color: Rgba<u8>, px: &mut P) {
+    /* we can expect about 2 * pi * r pixels to be 'on' for a circle of
+       radius r, we'll upper approximate 2 * pi by 20 */
+    
+    let rf64 = r as f64;
+    let n = 20 * r;
+    for i in 0..n {
+        let theta = 2.0 * PI * i as f64/ n as f64;
+        let dx = (theta.cos() * rf64).round() as i32; 
+        let dy = (theta.sin() * rf64).round() as i32;
+        plot(xc + dx, yc + dy, color, px);
+    }
+}
+
+/* Anti-aliased rendering: shapes are given in pixel coordinates, (x, y)
+   being the centre of pixel (x, y), and each pixel is blended with the
+   colour according to the part of it the shape covers. */
+
+/// Sub-scanlines per pixel row used to estimate polygon coverage.
+static SUBSAMPLES: usize = 4;
+
+/// Composites color over dst, color being scaled by coverage.
+pub fn blend(dst: Rgba<u8>, color: Rgba<u8>, coverage: f64) -> Rgba<u8> {
+    let sa = coverage.clamp(0.0, 1.0) * color[3] as f64 / 255.0;
+    let da = dst[3] as f64 / 255.0;
+    let oa = sa + da * (1.0 - sa);
+    if oa <= 0.0 { return Rgba([0, 0, 0, 0]); }
+
+    let mut out = [0_u8; 4];
+    for i in 0..3 {
+        let c = (color[i] as f64 * sa + dst[i] as f64 * da * (1.0 - sa)) / oa;
+        out[i] = c.round() as u8;
+    }
+    out[3] = (oa * 255.0).round() as u8;
+    Rgba(out)
+}
+
+/* Adds the coverage of the span [xa, xb) of a sub-scanline to the pixels of
+   a row starting at x0. */
+fn add_span(cover: &mut [f64], x0: i32, xa: f64, xb: f64, weight: f64) {
+    let xr = x0 + cover.len() as i32;
+    let (xa, xb) = (xa.max(x0 as f64), xb.min(xr as f64));
+    if xb <= xa { return; }
+
+    let first = xa.floor() as i32;
+    let last = (xb.ceil() as i32).min(xr);
+    for i in first..last {
+        let overlap = xb.min(i as f64 + 1.0) - xa.max(i as f64);
+        cover[(i - x0) as usize] += overlap * weight;
+    }
+}
+
+/// Fills the polygon with the non-zero winding rule, blending its edges.
+pub fn fill_polygon_aa<P: Pixels + ?Sized>(points: &[(f64, f64)],
+                                           color: Rgba<u8>, px: &mut P) {
+    fill_contours_aa(&[points.to_vec()], color, px);
+}
+
+/// Anti-aliased counterpart of fill_contours.
+pub fn fill_contours_aa<P: Pixels + ?Sized>(contours: &[Contour],
+                                            color: Rgba<u8>, px: &mut P) {
+    let (bx0, by0, bx1, by1) = px.bounds();
+    if bx1 <= bx0 { return; }
+    // from pixel centres to pixel corners
+    let shifted: Vec<Contour> = contours.iter().map(|c| {
+        c.iter().map(|(x, y)| (x + 0.5, y + 0.5)).collect()
+    }).collect();
+    let (y0, y1) = match rows(&shifted, by0, by1) {
+        Some(r) => r,
+        None => return,
+    };
+    let xs = shifted.iter().flat_map(|c| c.iter().map(|p| p.0));
+    let (xmin, xmax) = xs.fold((f64::MAX, f64::MIN),
+                               |(lo, hi), x| (lo.min(x), hi.max(x)));
+    if xmax <= bx0 as f64 || xmin >= bx1 as f64 { return; }
+    let x0 = xmin.max(bx0 as f64).floor() as i32;
+    let x1 = (xmax.ceil() as i32).min(bx1);
+
+    let weight = 1.0 / SUBSAMPLES as f64;
+    let mut cover = vec![0.0; (x1 - x0) as usize];
+    let mut crossings: Vec<(f64, i32)> = Vec::new();
+    for py in y0..y1 {
+        cover.iter_mut().for_each(|c| *c = 0.0);
+        for s in 0..SUBSAMPLES {
+            let sy = py as f64 + (s as f64 + 0.5) * weight;
+            scan_spans(&shifted, sy, &mut crossings, |xa, xb| {
+                add_span(&mut cover, x0, xa, xb, weight);
+            });
+        }
+        for (i, c) in cover.iter().enumerate() {
+            if *c > 0.0 {
+                px.blend(x0 + i as i32, py, color, *c);
+            }
+        }
+    }
+}
+
+/* Polygon of a segment of width t, with square ends on the endpoints. */
+fn segment_polygon(xi: f64, yi: f64, xf: f64, yf: f64, t: f64)
+    -> [(f64, f64); 4] {
+    let (dx, dy) = (xf - xi, yf - yi);
+    let len = (dx * dx + dy * dy).sqrt();
+    let (nx, ny) = if len > 0.0 { (-dy / len * t / 2.0, dx / len * t / 2.0) }
+                   else         { (0.0, t / 2.0) };
+    if len > 0.0 {
+        [(xi + nx, yi + ny), (xf + nx, yf + ny),
+         (xf - nx, yf - ny), (xi - nx, yi - ny)]
+    } else {
+        // a dot
+        [(xi - ny, yi - ny), (xi + ny, yi - ny),
+         (xi + ny, yi + ny), (xi - ny, yi + ny)]
+    }
+}
+
+/// Anti-aliased counterpart of draw_line2, one pixel wide, from and to
+/// points that need not be on pixel centres.
+pub fn draw_line2_aa<P: Pixels + ?Sized>(from: (f64, f64), to: (f64, f64),
+                                         color: Rgba<u8>, px: &mut P) {
+    let quad = segment_polygon(from.0, from.1, to.0, to.1, 1.0);
+    fill_polygon_aa(&quad, color, px);
+}
+
+/// Anti-aliased counterpart of draw_circle: a ring from radius r to
+/// r + t - 1, coverage coming from the distance to the centre.
+pub fn draw_circle_aa<P: Pixels + ?Sized>(xc: i32, yc: i32, r: usize,
+                                          t: usize, color: Rgba<u8>,
+                                          px: &mut P) {
+    let inner = r as f64 - 0.5;
+    let outer = (r + t.max(1)) as f64 - 0.5;
+
+    // only the part of the surrounding square lying in the bounds
+    let (bx0, by0, bx1, by1) = px.bounds();
+    let reach = outer.ceil() as i32 + 1;
+    let (x0, x1) = ((xc - reach).max(bx0), (xc + reach + 1).min(bx1));
+    let (y0, y1) = ((yc - reach).max(by0), (yc + reach + 1).min(by1));
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let (dx, dy) = ((x - xc) as f64, (y - yc) as f64);
+            let d = (dx * dx + dy * dy).sqrt();
+            let coverage = (outer - d + 0.5).min(d - inner + 0.5)
+                                            .clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                px.blend(x, y, color, coverage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thin_triangles_are_filled() {
+        let (w, h) = (16, 16);
+        let white = Rgba([255, 255, 255, 255]);
+        let red = Rgba([255, 0, 0, 255]);
+        // the centroid lies on the outline, which stopped the flood fill
+        for (a, b, c) in [((1, 1), (14, 2), (1, 3)),
+                          ((2, 14), (3, 1), (4, 14)),
+                          ((1, 1), (7, 7), (14, 14))] {
+            let mut buf = Buffer::new(w, h, white);
+            fill_polygon(&[a, b, c], red, &mut buf);
+            let tab = buf.pixels;
+            let filled = tab.iter().filter(|p| **p == red).count();
+            assert!(filled >= 12, "{:?} {:?} {:?}: {}", a, b, c, filled);
+            // nothing leaks out of the bounding box
+            for y in 0..h as i32 {
+                for x in 0..w as i32 {
+                    let inside = x >= a.0.min(b.0).min(c.0)
+                              && x <= a.0.max(b.0).max(c.0)
+                              && y >= a.1.min(b.1).min(c.1)
+                              && y <= a.1.max(b.1).max(c.1);
+                    assert!(inside || tab[y as usize * w + x as usize] == white);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn off_canvas_parts_are_clipped() {
+        let (w, h) = (16, 16);
+        let white = Rgba([255, 255, 255, 255]);
+        let red = Rgba([255, 0, 0, 255]);
+        let column = |tab: &[Rgba<u8>], x: usize| {
+            (0..h).filter(|y| tab[y * w + x] == red).count()
+        };
+
+        // the part left of the image used to be piled up on its first column
+        let mut buf = Buffer::new(w, h, white);
+        draw_line2(-20, 8, 4, 8, red, &mut buf);
+        assert_eq!(buf.pixels.iter().filter(|p| **p == red).count(), 5);
+        let mut buf = Buffer::new(w, h, white);
+        fill_polygon(&[(-10, 2), (3, 2), (-10, 12)], red, &mut buf);
+        assert!(column(&buf.pixels, 0) < 10);
+        assert_eq!(column(&buf.pixels, 4), 0);
+
+        // shapes entirely out of the image draw nothing
+        let mut buf = Buffer::new(w, h, white);
+        draw_circle(-30, 40, 5, 2, red, &mut buf);
+        draw_circle_aa(100, -8, 5, 2, red, &mut buf);
+        draw_line2_aa((-5.0, -5.0), (-1.0, 30.0), red, &mut buf);
+        assert!(buf.pixels.iter().all(|p| *p == white));
+    }
+
+    #[test]
+    fn sprites_match_direct_drawing() {
+        let (w, h) = (40, 30);
+        let white = Rgba([255, 255, 255, 255]);
+        let draw = |c: &mut dyn Canvas| {
+            c.fill(&[vec![(-6.0, -4.3), (7.2, -5.0), (0.4, 9.6)]],
+                   Rgba([0, 0, 0, 255]));
+            c.circle((0.0, -8.0), 4.0, 2.0, Rgba([0, 0, 160, 200]));
+            c.polygon(&[(-9.0, 0.0), (2.0, 3.0), (-3.0, 12.0)],
+                      Rgba([255, 0, 0, 128]));
+            c.line((-12.0, 8.0), (11.0, -7.0), 1.0, Rgba([0, 128, 0, 255]));
+        };
+        for antialias in [false, true] {
+            let mut sprites = RasterCanvas::new(Buffer::new(w, h, white),
+                                                antialias);
+            let mut direct = RasterCanvas::new(Buffer::new(w, h, white),
+                                               antialias);
+            // overlapping each other, and partly out of the image
+            for (x, y) in [(10, 12), (17, 15), (36, 2), (10, 12)] {
+                sprites.figure(0, x, y, 16, &draw);
+                direct.draw_at(x, y, &draw);
+            }
+            assert!(sprites.into_pixels().pixels
+                    == direct.into_pixels().pixels);
+        }
+    }
+
+    #[test]
+    fn figures_larger_than_the_pixels_are_clipped() {
+        let (w, h) = (20, 20);
+        let white = Rgba([255, 255, 255, 255]);
+        // far more rings and polygon than the image shows
+        let draw = |c: &mut dyn Canvas| {
+            c.circle((0.0, 0.0), 5.0, 3000.0, Rgba([0, 0, 160, 255]));
+            c.polygon(&[(-4000.0, 0.0), (4000.0, 1.0), (0.0, 4000.0)],
+                      Rgba([255, 0, 0, 255]));
+        };
+        let mut figure = RasterCanvas::new(Buffer::new(w, h, white), false);
+        let mut direct = RasterCanvas::new(Buffer::new(w, h, white), false);
+        figure.figure(0, 10, 10, 4000, &draw);
+        direct.draw_at(10, 10, &draw);
+        assert!(figure.sprites.is_empty());
+        assert!(figure.into_pixels().pixels == direct.into_pixels().pixels);
+    }
+
+    #[test]
+    fn polygon_coverage() {
+        let (w, h) = (8, 8);
+        let white = Rgba([255, 255, 255, 255]);
+        let mut buf = Buffer::new(w, h, white);
+        // pixel centres 2..=5 are covered, edges fall on pixel boundaries
+        fill_polygon_aa(&[(1.5, 1.5), (5.5, 1.5), (5.5, 5.5), (1.5, 5.5)],
+                        Rgba([0, 0, 0, 255]), &mut buf);
+        assert_eq!(buf.pixels[3 * w + 3], Rgba([0, 0, 0, 255]));
+        assert_eq!(buf.pixels[3 * w + 1], white);
+
+        // half covered pixels are mid grey
+        let mut buf = Buffer::new(w, h, white);
+        fill_polygon_aa(&[(1.5, 1.5), (5.0, 1.5), (5.0, 5.5), (1.5, 5.5)],
+                        Rgba([0, 0, 0, 255]), &mut buf);
+        assert_eq!(buf.pixels[3 * w + 5], Rgba([128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn blended_lines_keep_their_place_between_pixels() {
+        let (w, h) = (8, 8);
+        let white = Rgba([255, 255, 255, 255]);
+        let grey = Rgba([128, 128, 128, 255]);
+        // a level line between two rows of pixels covers half of each
+        let mut canvas = RasterCanvas::new(Buffer::new(w, h, white), true);
+        canvas.line((1.0, 3.5), (6.0, 3.5), 1.0, Rgba([0, 0, 0, 255]));
+        let pixels = canvas.into_pixels().pixels;
+        assert_eq!(pixels[3 * w + 3], grey);
+        assert_eq!(pixels[4 * w + 3], grey);
+    }
+}
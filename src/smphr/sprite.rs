@@ -0,0 +1,75 @@
+use super::raster::Pixels;
+use image::Rgba;
+use std::collections::HashSet;
+
+#[derive(Clone, Copy)]
+enum Op {
+    Set(Rgba<u8>),
+    Blend(Rgba<u8>, f64),
+}
+
+/// Pixels remembering what is written on them instead of storing it, to
+/// make a sprite.
+pub struct Recorder {
+    ops: Vec<(i32, i32, Op)>,
+    /* how far from the origin writes are kept, either way */
+    reach: i32,
+}
+
+impl Recorder {
+    /// Recorder of the writes up to reach from the origin, the others being
+    /// clipped.
+    pub fn new(reach: i32) -> Recorder {
+        Recorder { ops: Vec::new(), reach }
+    }
+
+    /// Sprite replaying the writes, leaving out those overwritten later.
+    pub fn into_sprite(self) -> Sprite {
+        let mut overwritten = HashSet::new();
+        let mut ops: Vec<(i32, i32, Op)> = self.ops.into_iter().rev()
+            .filter(|(x, y, op)| {
+                let kept = !overwritten.contains(&(*x, *y));
+                if let Op::Set(_) = op { overwritten.insert((*x, *y)); }
+                kept
+            })
+            .collect();
+        ops.reverse();
+        Sprite { ops }
+    }
+}
+
+impl Pixels for Recorder {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
+        (-self.reach, -self.reach, self.reach + 1, self.reach + 1)
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: Rgba<u8>) {
+        self.ops.push((x, y, Op::Set(color)));
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: Rgba<u8>, coverage: f64) {
+        self.ops.push((x, y, Op::Blend(color, coverage)));
+    }
+}
+
+/// Drawing made once and copied anywhere. The writes are replayed in the
+/// same order, so that blending over what lies below gives the same pixels
+/// as drawing again.
+pub struct Sprite {
+    ops: Vec<(i32, i32, Op)>,
+}
+
+impl Sprite {
+    /// Writes the sprite with its origin at (x, y).
+    pub fn paste<P: Pixels + ?Sized>(&self, px: &mut P, x: i32, y: i32) {
+        let (x0, y0, x1, y1) = px.bounds();
+        for (dx, dy, op) in self.ops.iter() {
+            let (x, y) = (x + dx, y + dy);
+            if x < x0 || y < y0 || x >= x1 || y >= y1 { continue; }
+            match *op {
+                Op::Set(color) => px.set(x, y, color),
+                Op::Blend(color, coverage) => px.blend(x, y, color, coverage),
+            }
+        }
+    }
+}
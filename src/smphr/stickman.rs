@@ -1,5 +1,9 @@
-use super::SmphrParams;
-use std::f64::consts::{PI, FRAC_PI_4};
+use super::{SmphrParams, Viewpoint};
+use super::alphabet::{Pose, Sign};
+use super::layout::Writing;
+use super::canvas::Canvas;
+use super::stroke::{stroke, Cap};
+use std::f64::consts::FRAC_PI_4;
 
 static RIGHT_FOOT:     Point = Point { x: 10, y:  40 };
 static LEFT_FOOT:      Point = Point { x: -10,  y: 40 };
@@ -11,7 +15,9 @@ static NECK:           Point = Point { x: 0, y: -10 };
 static NOSE:           Point = Point { x: 0, y: -20 };
 static HEAD_SIZE:      usize = (NECK.y - NOSE.y) as usize;
 static ARM_LENGTH:     i32 = 30;
-static FLAG_LENGTH:    i32 = 10;
+/// Default side of the square flags.
+pub static FLAG_LENGTH: i32 = 10;
+static GROUP_MARK:     i32 = 5;
 static X_MARGIN:       i32 = 1;
 static Y_MARGIN:       i32 = 1;
 
@@ -20,24 +26,9 @@ static BODY_THICKNESS:     usize = 10;
 static LEG_THICKNESS:      usize = 3;
 
 static STICK_WIDTH:    i32 = 2*(LEFT_SHOULDER.x + ARM_LENGTH) + X_MARGIN;
-static STICK_HEIGHT:   i32 = RIGHT_FOOT.y - RIGHT_SHOULDER.y
-                             + ARM_LENGTH + Y_MARGIN;
-
-// static WHITE: u8 = 0;
-static BLACK: u8 = 1;
-static RED:   u8 = 2;
-
-//                                  a  b  c  d  e  f  g  h  i
-//                                  j  k  l  m  n  o  p  q  r
-//                                  s  t  u  v  w  x  y  z
-/// Positions of the right arm for each letter
-static RIGHT_ARM_POS: &[u8; 26] = &[1, 2, 3, 4, 0, 0, 0, 1, 1,
-                                    4, 1, 1, 1, 1, 2, 2, 2, 2,
-                                    2, 3, 3, 4, 5, 5, 3, 6];
-/// Positions of the left arm for each letter
-static LEFT_ARM_POS:  &[u8; 26] = &[0, 0, 0, 0, 5, 6, 7, 2, 3,
-                                    6, 4, 5, 6, 7, 3, 4, 5, 6,
-                                    7, 4, 5, 7, 6, 7, 6, 7];
+/// Height of a row of stickmen, at scale 1.
+pub static STICK_HEIGHT: i32 = RIGHT_FOOT.y - RIGHT_SHOULDER.y
+                               + ARM_LENGTH + Y_MARGIN;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Point {
@@ -52,25 +43,11 @@ impl Point {
             y,
         }
     }
-
-    fn increment(&self, params: &SmphrParams) -> Result<Point, StickmanError> {
-        if self.x + STICK_WIDTH >= params.width as i32 {
-            // we could cut their legs
-            // stickmen are not alive so that we can hurt them
-            if self.y + STICK_HEIGHT > params.height as i32 {
-                return Err(StickmanError::VerticalOverflow);
-            }
-            Ok(Point::from(STICK_WIDTH / 2, self.y + STICK_HEIGHT))
-        } else {
-            Ok(Point::from(self.x + STICK_WIDTH, self.y))
-        }
-    }
 }
 
 #[derive(Debug)]
 pub enum StickmanError {
     InvalidCharacter(char),
-    VerticalOverflow,
 }
 
 impl std::fmt::Display for StickmanError {
@@ -78,7 +55,6 @@ impl std::fmt::Display for StickmanError {
         match self {
             Self::InvalidCharacter(c) => { 
                 write!(f, "invalid character '{}'", c) }
-            Self::VerticalOverflow => { write!(f, "vertical overflow") }
         }
     }
 }
@@ -108,48 +84,33 @@ impl Stickman {
         }
     }
 
-    // TODO: handle numeric digits
-    pub fn first_from_letter(c: char) -> Result<Stickman, StickmanError> {
-        if !c.is_ascii_alphanumeric() && c != ' ' && c != '\n' {
-            return Err(StickmanError::InvalidCharacter(c));
-        }
-
-        let v = c.to_ascii_lowercase();
-        let i = if v.is_alphabetic() { (v as u8 - 'a' as u8) as usize } 
-                else { 0 };
-
+    fn from_pose(stype: StickmanType, pose: Pose) -> Stickman {
         let mut s: Stickman = Stickman::new();
 
-        s.stype = match c {
-            ' ' => StickmanType::Space,
-            '\n' => StickmanType::CarriageReturn,
-            _ => StickmanType::Character,
-        };
-
-        match s.stype {
-            StickmanType::Character => {},
-            _ => { return Ok(s); }
-        }
-
-        s.right_hand = RIGHT_ARM_POS[i];
-        s.left_hand = LEFT_ARM_POS[i];
-        s.cg = Point::from(STICK_WIDTH / 2, STICK_HEIGHT / 2);
-        s.stype = StickmanType::Character;
+        s.stype = stype;
+        s.right_hand = pose.right;
+        s.left_hand = pose.left;
 
-        Ok(s)
+        s
     }
 
-    pub fn from_letter_with_prev(c: char, prev_pos: &Point, 
-        params: &SmphrParams)
-        -> Result<Stickman, StickmanError> {
-
-        match Stickman::first_from_letter(c) {
-            Err(e) => Err(e),
-            Ok(s) => Ok(s.set_pos(prev_pos.increment(params)?))
+    /// One stickman per pose of the sign, yet to be placed.
+    pub fn from_sign(sign: &Sign) -> Vec<Stickman> {
+        let rest = Pose::from(0, 0);
+        match sign {
+            Sign::Space => vec![Stickman::from_pose(StickmanType::Space, 
+                                                    rest)],
+            Sign::NewLine => vec![
+                Stickman::from_pose(StickmanType::CarriageReturn, rest)],
+            Sign::Pose(p) => vec![
+                Stickman::from_pose(StickmanType::Character, *p)],
+            Sign::Strokes(v) => v.iter()
+                .map(|p| Stickman::from_pose(StickmanType::Character, *p))
+                .collect(),
         }
     }
 
-    fn set_pos(&self, pos: Point) -> Stickman {
+    pub fn set_pos(&self, pos: Point) -> Stickman {
         Stickman {
             right_hand: self.right_hand,
             left_hand: self.left_hand,
@@ -162,281 +123,205 @@ impl Stickman {
         &self.cg
     }
 
-    pub fn draw(&self, tab: &mut [u8], params: &SmphrParams) {
+    pub fn draw(&self, canvas: &mut dyn Canvas, params: &SmphrParams) {
         match self.stype {
             StickmanType::Space => { return; },
             StickmanType::CarriageReturn => { return; },
             StickmanType::Unknown => { println!("?"); return; }
             _ => {}
         }
-        self.draw_body(tab, params);
-
-        let (tabw, tabh) = (params.width as usize, params.height as usize);
-        self.draw_arm(self.right_hand, true, tab, tabw, tabh);
-        self.draw_arm(self.left_hand, false, tab, tabw, tabh);
+        // drawn around its centre, figures with the same pose look the same
+        let key = (self.right_hand as u32) << 8 | self.left_hand as u32;
+        let reach = reach(params);
+        canvas.figure(key, self.cg.x, self.cg.y, reach, &|canvas| {
+            self.draw_body(canvas, params);
+
+            self.draw_arm(self.right_hand, true, canvas, params);
+            self.draw_arm(self.left_hand, false, canvas, params);
+        });
     }
 
-    fn draw_body(&self, tab: &mut [u8], params: &SmphrParams) {
-        let (x, y) = (self.cg.x, self.cg.y);
-        let (tabw, tabh) = (params.width as usize, params.height as usize);
-        // body:
-        draw_line(x + NECK.x, y + NECK.y, x + TRUC.x, y + TRUC.y,
-                  BODY_THICKNESS, BLACK, tab, tabw, tabh);
-
-        draw_line(x + TRUC.x, y + TRUC.y,
-                  x + LEFT_FOOT.x, y + LEFT_FOOT.y,
-                  LEG_THICKNESS, BLACK, tab, tabw, tabh);
-        draw_line(x + TRUC.x, y + TRUC.y,
-                  x + RIGHT_FOOT.x, y + RIGHT_FOOT.y,
-                  LEG_THICKNESS, BLACK, tab, tabw, tabh);
+    fn draw_body(&self, canvas: &mut dyn Canvas, params: &SmphrParams) {
+        let mirror = params.view == Viewpoint::Signaller;
+        let colors = &params.palette;
+        // body, as a single shape so that legs and torso join without seams;
+        // seen from behind the signaller, left and right are swapped
+        let s = params.scale;
+        let at = |p: Point| ((if mirror { -p.x } else { p.x }) as f64 * s,
+                             p.y as f64 * s);
+        let k = params.thickness * s;
+        let torso = BODY_THICKNESS as f64 * k;
+        // the cap at the top goes no further than the neck, out of the head
+        let top = match params.cap {
+            Cap::Butt => at(NECK),
+            _ => {
+                let (nx, ny) = at(NECK);
+                (nx, (ny + torso / 2.0).min(TRUC.y as f64 * s))
+            }
+        };
+        let mut skeleton = stroke(&[top, at(TRUC)], torso,
+                                  params.cap, params.join);
+        skeleton.extend(stroke(&[at(LEFT_FOOT), at(TRUC), at(RIGHT_FOOT)],
+                               LEG_THICKNESS as f64 * k,
+                               params.cap, params.join));
+        canvas.fill(&skeleton, colors.body);
         // head
-        draw_circle((x + NOSE.x) as usize, (y + NOSE.y) as usize, HEAD_SIZE, 
-                    HEAD_THICKNESS, BLACK, tab, tabw, tabh);
+        canvas.circle(at(NOSE), HEAD_SIZE as f64 * s, HEAD_THICKNESS as f64 * k,
+                      colors.head);
     }
 
-    fn draw_arm(&self, n: u8, is_right: bool, 
-        tab: &mut [u8], tabw: usize, tabh: usize) {
+    fn draw_arm(&self, n: u8, is_right: bool, canvas: &mut dyn Canvas,
+        params: &SmphrParams) {
 
         if n == 0 { return; }
-        let (x, y) = (self.cg.x, self.cg.y);
+        let mirror = params.view == Viewpoint::Signaller;
+        let colors = &params.palette;
 
         let alpha = FRAC_PI_4 * (n as f64 - 2.0);
         let (sina, cosa) = alpha.sin_cos();
-        let (armsin, armcos) = (ARM_LENGTH as f64 * sina, 
+        let (armsin, armcos) = (ARM_LENGTH as f64 * sina,
                                 ARM_LENGTH as f64 * cosa);
-        let (fsin, fcos) = (FLAG_LENGTH as f64 * sina, 
-                            FLAG_LENGTH as f64 * cosa);
+        let flag = params.flag_size as f64;
+        let (fsin, fcos) = (flag * sina, flag * cosa);
         let shoulder = if is_right { RIGHT_SHOULDER } else { LEFT_SHOULDER };
-
-        let (x0, y0) = (x + shoulder.x - armcos as i32, 
-                        y + shoulder.y - armsin as i32);
-        let (x1, y1) = (x0 + fcos as i32, 
-                        y0 + fsin as i32);
-        let (x2, y2) = if n <= 4 { (x1 - fsin as i32, 
-                                    y1 + fcos as i32) }
-                       else      { (x1 + fsin as i32,
-                                    y1 - fcos as i32) };
-        let (x3, y3) = if n <= 4 { (x0 - fsin as i32,
-                                    y0 + fcos as i32) }
-                       else      { (x0 + fsin as i32,
-                                    y0 - fcos as i32) };
-        
-        // draw arm
-        draw_line2((x + shoulder.x) as usize, (y + shoulder.y) as usize, 
-                    x0 as usize, y0 as usize, BLACK, tab, tabw, tabh);        
+        let (xs, ys) = (shoulder.x as f64, shoulder.y as f64);
+
+        // kept in fractions of a pixel until scaled
+        let (x0, y0) = (xs - armcos, ys - armsin);
+        let (x1, y1) = (x0 + fcos, y0 + fsin);
+        let (dx, dy) = if n <= 4 { (-fsin, fcos) } else { (fsin, -fcos) };
+        let (x2, y2) = (x1 + dx, y1 + dy);
+        let (x3, y3) = (x0 + dx, y0 + dy);
+
+        // the flag is mirrored with the arm holding it
+        let s = params.scale;
+        let at = |x: f64, y: f64| (if mirror { -x } else { x } * s, y * s);
+        let (p0, p1, p2, p3) = (at(x0, y0), at(x1, y1), at(x2, y2), at(x3, y3));
+
+        // draw arm, as wide as the figure is scaled
+        canvas.line(at(xs, ys), p0, s, colors.body);
+        // the pole runs from p1 to its top, p0, and the flag is split along
+        // the diagonal from p1 to the upper corner of the fly, p3
+        canvas.polygon(&[p0, p1, p3], colors.flag);
+        canvas.polygon(&[p1, p2, p3], colors.flag_fly);
         // draw flag lines
-        if n <= 4 {
-            draw_line2(x1 as usize, y1 as usize, x3 as usize, y3 as usize, 
-                       BLACK, tab, tabw, tabh);
-        } else {
-            draw_line2(x0 as usize, y0 as usize, x3 as usize, y3 as usize, 
-                       BLACK, tab, tabw, tabh);
-        }
-        draw_line2(x3 as usize, y3 as usize, x2 as usize, y2 as usize, BLACK, tab, tabw, tabh);
-        draw_line2(x2 as usize, y2 as usize, x1 as usize, y1 as usize, BLACK, tab, tabw, tabh);
-        // red triangle:
-        if n <= 4 {
-            fill_triangle(x0 as usize, y0 as usize, x1 as usize, y1 as usize,
-                          x3 as usize, y3 as usize, RED, tab, tabw, tabh)
-        } else {
-            fill_triangle(x0 as usize, y0 as usize, x1 as usize, y1 as usize,
-                          x2 as usize, y2 as usize, RED, tab, tabw, tabh)
-        }
+        let outline = colors.flag_outline;
+        canvas.line(p0, p3, s, outline);
+        canvas.line(p3, p2, s, outline);
+        canvas.line(p2, p1, s, outline);
     }
 }
 
-/* Returns (x, y) such as they are bounded by tabw and tabh
- */
-fn bound(x: usize, y: usize, tabw: usize, tabh: usize) 
-    -> (usize, usize) {
-    let x2 = if x >= tabw {  tabw-1  } else {  x  };
-    let y2 = if y >= tabh {  tabh-1  } else {  y  };
-
-    (x2, y2)
+/* Length on the image of a length of the figures, in pixels. */
+fn scaled(len: i32, params: &SmphrParams) -> i32 {
+    (len as f64 * params.scale).round() as i32
 }
 
-/** Draws the line (xi, yi) -- (xf, yf) in array tab. */
-fn draw_line2(xi: usize, yi: usize, xf: usize, yf: usize, color: u8,
-             tab: &mut [u8], tabw: usize, tabh: usize) {
-    
-    let (xi2, yi2) = bound(xi, yi, tabw, tabh);
-    let (xf2, yf2) = bound(xf, yf, tabw, tabh);
-
-    assert!(xi2 < tabw && xf2 < tabw);
-    assert!(yi2 < tabh && yf2 < tabh);
-    let (x1, x2, x_inversed) = if xf2 < xi2 { (xf2, xi2, true)   } 
-                               else         { (xi2, xf2, false)  };
-    let (y1, y2, y_inversed) = if yf2 < yi2 { (yf2, yi2, true)   }
-                               else         { (yi2, yf2, false)  };
-    
-    if x1 == x2 {
-        if y1 == y2 {   tab[y1*tabw + x1] = color; 
-                        return }
-        for i in y1..=y2 {   tab[i*tabw + x1] = color;   }
-        return
-    }
-    if y1 == y2 {
-        for i in x1..=x2 {   tab[y1*tabw + i] = color;   }
-        return
-
-    }
-
-    let mut y = y1;
-    let (dx, dy) = ((x2 as i32 - x1 as i32), (y2 as i32 - y1 as i32));
-    let mut e: i32 = -dx;
-    let (ex, ey) = (2*dy, -2*dx);
-
-
-    if !x_inversed {
-        if !y_inversed { // quadrant 4
-            for x in x1..x2 {
-                tab[y*tabw + x] = color;
-
-                e += ex;
-                while e >= 0 {
-                    y += 1;
-                    e += ey;
-                    if e >= 0 {   tab[y*tabw + x] = color;   }
-                }
-            }
-        }
-        else { // quadrant 1 
-            for x in x1..x2 {
-                tab[(y1+y2-y)*tabw + x] = color;
-
-                e += ex;
-                while e >= 0 {
-                    y += 1;
-                    e += ey;
-                    if e >= 0 {   tab[(y1+y2-y)*tabw + x] = color;   }
-                }
-            }
-        }
-    }
-    else { 
-        if !y_inversed { // quadrant 3
-            for x in x1..x2 {
-                tab[y*tabw + x1+x2-x] = color;
-
-                e += ex;
-                while e >= 0 {
-                    y += 1;
-                    e += ey;
-                    if e >= 0 {   tab[y*tabw + x1+x2-x] = color;   }
-                }
-            }
-        }
-        else { // quadrant 2
-            for x in x1..x2 {
-                tab[(y1+y2-y)*tabw + x1+x2-x] = color;
-
-                e += ex;
-                while e >= 0 {
-                    y += 1;
-                    e += ey;
-                    if e >= 0 {   tab[(y1+y2-y)*tabw + x1+x2-x] = color;   }
-                }
-            }
-        }
-    }
+/// Width taken by a stickman on the image, in pixels.
+pub fn stick_width(params: &SmphrParams) -> i32 {
+    scaled(STICK_WIDTH, params).max(1)
 }
 
-fn draw_triangle(xa: usize, ya: usize,
-                 xb: usize, yb: usize,
-                 xc: usize, yc: usize, color: u8, tab: &mut [u8],
-                 tabw: usize, tabh: usize) {
-
-    draw_line2(xa, ya, xb, yb, color, tab, tabw, tabh);
-    draw_line2(xa, ya, xc, yc, color, tab, tabw, tabh);
-    draw_line2(xb, yb, xc, yc, color, tab, tabw, tabh);
+/// Height of a row of stickmen on the image, in pixels.
+pub fn stick_height(params: &SmphrParams) -> i32 {
+    scaled(STICK_HEIGHT, params).max(1)
 }
 
-fn fill_triangle(xa: usize,   ya: usize,
-                 xb: usize,   yb: usize, 
-                 xc: usize,   yc: usize, color: u8, tab: &mut [u8], 
-                 tabw: usize, tabh: usize) {
-
-    draw_triangle(xa, ya, xb, yb, xc, yc, color, tab, tabw, tabh);
-
-    let (x0, y0) = ((xa + xb + xc) / 3, (ya + yb + yc) / 3);
-    let mut queue = Vec::<(usize, usize)>::new();
-    queue.push((x0, y0));
-    if tab[y0 * tabw + x0] == color { return; }
-    while let Some(p) = queue.pop() {
-        check(bound(p.0+1, p.1, tabw, tabh), color, tab, tabw, &mut queue);
-        if p.0 > 0 {
-            check(bound(p.0-1, p.1, tabw, tabh), color, tab, tabw, &mut queue);
-        }
-        check(bound(p.0, p.1+1, tabw, tabh), color, tab, tabw, &mut queue);
-        if p.1 > 0 {
-            check(bound(p.0, p.1-1, tabw, tabh), color, tab, tabw, &mut queue);
-        }
-    }
+/// Bound on how far the flags and strokes of a stickman can stick out of
+/// the box it is laid out in.
+pub fn overhang(params: &SmphrParams) -> i32 {
+    let k = params.thickness;
+    let strokes = (BODY_THICKNESS + LEG_THICKNESS) as f64 / 2.0;
+    let flag = params.flag_size.abs() as f64 * std::f64::consts::SQRT_2;
+    ((flag + strokes * k + 1.0) * params.scale).ceil() as i32
 }
 
-fn check((x, y): (usize, usize), color: u8, tab: &mut [u8], tabw: usize, 
-         queue: &mut Vec<(usize, usize)>) {
-
-    if tab[y * tabw + x] != color {
-        queue.push((x, y));
-    }
-
-    tab[y * tabw + x] = color;
+/// Bound on how far above or below its centre a stickman, its flags or the
+/// mark under its group can be drawn.
+pub fn reach(params: &SmphrParams) -> i32 {
+    let k = params.thickness;
+    let strokes = (BODY_THICKNESS + HEAD_THICKNESS + LEG_THICKNESS) as f64;
+    let reach = (STICK_HEIGHT + GROUP_MARK + 2 * params.flag_size.abs()) as f64
+                + strokes * k;
+    (reach * params.scale).ceil() as i32
 }
 
-fn draw_line(xi: i32, yi: i32, xf: i32, yf: i32, t: usize, color: u8,
-             tab: &mut [u8], tabw: usize, tabh: usize) {
-
-    let u = t / 2;
-    let alpha = ((yf - yi) as f64).atan2((xf - xi) as f64);
-    let (sina, cosa) = alpha.sin_cos();
-
-    let (x0, y0) = ((xi as f64 + u as f64 * sina) as usize,
-                    (yi as f64 + u as f64 * cosa) as usize);
-    let (x1, y1) = ((xi as f64 - u as f64 * sina) as usize,
-                    (yi as f64 - u as f64 * cosa) as usize);
-    let (x2, y2) = ((xf as f64 + u as f64 * sina) as usize,
-                    (yf as f64 + u as f64 * cosa) as usize);
-    let (x3, y3) = ((xf as f64 - u as f64 * sina) as usize,
-                    (yf as f64 - u as f64 * cosa) as usize);
-
-    if t > 2 {
-        fill_triangle(x0, y0, x1, y1, x2, y2, color, tab, tabw, tabh);
-        fill_triangle(x2, y2, x3, y3, x1, y1, color, tab, tabw, tabh)
-    } else {
-        draw_triangle(x0, y0, x1, y1, x2, y2, color, tab, tabw, tabh);
-        draw_triangle(x2, y2, x3, y3, x1, y1, color, tab, tabw, tabh)
-    }
+/// Room left beside a row, or a column, of stickmen for the mark under the
+/// groups it holds.
+pub fn mark_room(params: &SmphrParams) -> i32 {
+    scaled(GROUP_MARK + 1, params).max(3)
 }
 
-fn draw_circle(xc: usize, yc: usize, r: usize, t: usize, color: u8,
-               tab: &mut [u8], tabw: usize, tabh: usize) {
-
-    for i in 0..t {
-        draw_circle2(xc, yc, r + i, color, tab, tabw, tabh);
+/// Underlines the stickmen spelling a single character, so that the reader
+/// sees where it ends. In columns, the mark runs down their side, in the
+/// room left before the next column.
+pub fn draw_group(group: &[Stickman], canvas: &mut dyn Canvas,
+                  params: &SmphrParams) {
+    if group.len() < 2 { return; }
+
+    let (w, h) = (stick_width(params), stick_height(params));
+    let mark = scaled(GROUP_MARK, params);
+    // in the middle of the room left after the row, or the column
+    let room = mark_room(params);
+    let color = params.palette.body;
+    let l = &params.layout;
+    if l.writing == Writing::Vertical && l.grid.is_none() {
+        for column in group.chunk_by(|a, b| a.cg.x == b.cg.x) {
+            let ys = column.iter().map(|s| s.cg.y);
+            let (yi, yf) = (ys.clone().min().unwrap() - h / 2 + mark,
+                            ys.max().unwrap() + h / 2 - mark);
+            // the next column, and so the room, is on the left from right
+            // to left
+            let left = column[0].cg.x - w / 2;
+            let x = if l.rtl { left - (room - room / 2) }
+                    else     { left + w + room / 2 };
+            let x = x as f64;
+            canvas.line((x, yi as f64), (x, yf as f64), params.scale, color);
+        }
+        return;
     }
-}
-fn draw_circle2(xc: usize, yc: usize, r: usize, color: u8, 
-               tab: &mut [u8], tabw: usize, tabh: usize) {
-    /* we can expect about 2 * pi * r pixels to be 'on' for a circle of
-       radius r, we'll upper approximate 2 * pi by 20 */
-    
-    let rf64 = r as f64;
-    let n = 20 * r;
-    for i in 0..n {
-        let theta = 2.0 * PI * i as f64/ n as f64;
-        let dx = (theta.cos() * rf64).round() as i32; 
-        let dy = (theta.sin() * rf64).round() as i32;
-        let (x, y) = bound((xc as i32 + dx) as usize, 
-                           (yc as i32 + dy) as usize, tabw, tabh);
-        tab[y*tabw + x] = color;
+    for row in group.chunk_by(|a, b| a.cg.y == b.cg.y) {
+        let y = row[0].cg.y - h / 2 + h + room / 2;
+        // from right to left, the first figure is the rightmost one
+        let xs = row.iter().map(|s| s.cg.x);
+        let (xi, xf) = (xs.clone().min().unwrap() - w / 2 + mark,
+                        xs.max().unwrap() + w / 2 - mark);
+        let y = y as f64;
+        canvas.line((xi as f64, y), (xf as f64, y), params.scale, color);
     }
 }
 
+#[cfg(test)]
 mod test {
+    use super::*;
+    use super::super::raster::{Buffer, RasterCanvas};
+
+    #[test]
+    fn flags_are_red_in_the_upper_hoist() {
+        let mut params = SmphrParams::default();
+        params.scale = 4.0;
+        let white = params.palette.background;
+        let (red, yellow) = (params.palette.flag, params.palette.flag_fly);
+        // right arm held out level, the flag hanging under it from x = 60,
+        // the top of the pole, to x = 100, and from y = 110 to 150
+        let figure = Stickman::from_sign(&Sign::Pose(Pose::from(2, 0)))[0]
+            .set_pos(Point::from(200, 150));
+        let mut canvas = RasterCanvas::new(Buffer::new(400, 300, white), false);
+        figure.draw(&mut canvas, &params);
+        let pixels = canvas.into_pixels();
+        let at = |x: usize, y: usize| pixels.pixels()[y * 400 + x];
+
+        for x in 64..=92 {
+            assert_eq!(at(x, 114), red, "under the pole at {}", x);
+        }
+        for y in 120..=146 {
+            assert_eq!(at(96, y), yellow, "at the fly at {}", y);
+        }
+    }
+
     #[test]
     fn truc() {
-        let x = (2.0 as f64).atan2(0.0);
+        let x = 2.0_f64.atan2(0.0);
         println!("x: {}", x); // output: pi/2
     }
 }
\ No newline at end of file
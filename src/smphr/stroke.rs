@@ -0,0 +1,138 @@
+use std::f64::consts::PI;
+
+/// Outline of a filled shape, made of one or more closed contours.
+pub type Contour = Vec<(f64, f64)>;
+
+/// Shape of the ends of a stroke.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cap {
+    /// Stops at the endpoint.
+    Butt,
+    /// Goes on for half the width past the endpoint.
+    Square,
+    /// Ends with a half disc centred on the endpoint.
+    Round,
+}
+
+/// Shape of the corners between two segments of a stroke.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Join {
+    Round,
+    /// Sharp corner, cut when it would go further than MITER_LIMIT half
+    /// widths from the vertex.
+    Miter,
+}
+
+static MITER_LIMIT: f64 = 4.0;
+
+/// Outline of the polyline drawn with the given width. The contours all
+/// turn the same way so that, filled together with the non-zero winding
+/// rule, they form a single shape without seams.
+pub fn stroke(points: &[(f64, f64)], width: f64, cap: Cap, join: Join)
+    -> Vec<Contour> {
+
+    let hw = width / 2.0;
+    let mut contours = Vec::new();
+    if points.is_empty() || hw <= 0.0 { return contours; }
+
+    let last = points.len() - 1;
+    for i in 0..last {
+        let (mut a, mut b) = (points[i], points[i + 1]);
+        let d = match direction(a, b) {
+            Some(d) => d,
+            None => continue,
+        };
+        if cap == Cap::Square {
+            if i == 0    { a = (a.0 - d.0 * hw, a.1 - d.1 * hw); }
+            if i + 1 == last { b = (b.0 + d.0 * hw, b.1 + d.1 * hw); }
+        }
+        let n = (-d.1 * hw, d.0 * hw);
+        contours.push(vec![(a.0 + n.0, a.1 + n.1), (b.0 + n.0, b.1 + n.1),
+                           (b.0 - n.0, b.1 - n.1), (a.0 - n.0, a.1 - n.1)]);
+    }
+
+    for i in 1..last {
+        let p = points[i];
+        let (d1, d2) = match (direction(points[i - 1], p),
+                              direction(p, points[i + 1])) {
+            (Some(d1), Some(d2)) => (d1, d2),
+            _ => continue,
+        };
+        match join {
+            Join::Round => contours.push(disc(p, hw)),
+            Join::Miter => contours.push(miter(p, d1, d2, hw)),
+        }
+    }
+
+    if cap == Cap::Round || contours.is_empty() {
+        // a polyline reduced to a point still shows as a dot
+        contours.push(disc(points[0], hw));
+        contours.push(disc(points[last], hw));
+    }
+
+    contours.into_iter().map(oriented).collect()
+}
+
+fn direction(a: (f64, f64), b: (f64, f64)) -> Option<(f64, f64)> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len == 0.0 { None } else { Some((dx / len, dy / len)) }
+}
+
+/// Polygon approximating the disc, with sides of about a pixel.
+pub fn disc(c: (f64, f64), r: f64) -> Contour {
+    let n = ((2.0 * PI * r).ceil() as usize).max(8);
+    (0..n).map(|i| {
+        let theta = 2.0 * PI * i as f64 / n as f64;
+        (c.0 + r * theta.cos(), c.1 + r * theta.sin())
+    }).collect()
+}
+
+/* Corner on the outer side of the turn at p, going from direction d1 to d2,
+   mitered or bevelled past the limit. */
+fn miter(p: (f64, f64), d1: (f64, f64), d2: (f64, f64), hw: f64) -> Contour {
+    let cross = d1.0 * d2.1 - d1.1 * d2.0;
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let n1 = (-d1.1 * side, d1.0 * side);
+    let n2 = (-d2.1 * side, d2.0 * side);
+    let a = (p.0 + n1.0 * hw, p.1 + n1.1 * hw);
+    let b = (p.0 + n2.0 * hw, p.1 + n2.1 * hw);
+
+    let (mx, my) = (n1.0 + n2.0, n1.1 + n2.1);
+    let mlen = (mx * mx + my * my).sqrt();
+    if mlen == 0.0 { return vec![p, a, b]; }
+    let (mx, my) = (mx / mlen, my / mlen);
+    // distance from p to the miter point, in half widths
+    let ratio = 1.0 / (mx * n1.0 + my * n1.1);
+    if ratio > MITER_LIMIT {
+        return vec![p, a, b];
+    }
+    vec![p, a, (p.0 + mx * hw * ratio, p.1 + my * hw * ratio), b]
+}
+
+/* The contour turning clockwise on screen. */
+fn oriented(mut c: Contour) -> Contour {
+    let mut area = 0.0;
+    for i in 0..c.len() {
+        let (a, b) = (c[i], c[(i + 1) % c.len()]);
+        area += a.0 * b.1 - b.0 * a.1;
+    }
+    if area < 0.0 { c.reverse(); }
+    c
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn miter_is_cut_past_the_limit() {
+        // right angle: the corner reaches sqrt(2) half widths away
+        let c = miter((0.0, 0.0), (1.0, 0.0), (0.0, 1.0), 1.0);
+        assert_eq!(c.len(), 4);
+        // nearly a U-turn: bevelled
+        let d = (-(1.0_f64 - 0.01 * 0.01).sqrt(), 0.01);
+        let c = miter((0.0, 0.0), (1.0, 0.0), d, 1.0);
+        assert_eq!(c.len(), 3);
+    }
+}
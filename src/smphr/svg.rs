@@ -0,0 +1,164 @@
+use super::canvas::Canvas;
+use super::pdf::num;
+use super::stroke::Contour;
+use image::Rgba;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// SVG image drawn in pixels, from the top left corner.
+pub struct Svg {
+    width: u32,
+    height: u32,
+    /* printed size, in points */
+    size: (f64, f64),
+    body: String,
+    /* group drawing each figure key, defined once and used everywhere */
+    figures: BTreeMap<u32, String>,
+}
+
+impl Svg {
+    /// Image of width by height pixels, printed at size, in points, and
+    /// painted with the background colour.
+    pub fn new(width: u32, height: u32, size: (f64, f64),
+               background: Rgba<u8>) -> Svg {
+        let mut svg = Svg {
+            width,
+            height,
+            size,
+            body: String::new(),
+            figures: BTreeMap::new(),
+        };
+        let (w, h) = (width as f64, height as f64);
+        // the background covers whole pixels, not their centres
+        svg.element(background, &["fill"], |fill| {
+            format!("<rect x=\"-0.5\" y=\"-0.5\" width=\"{}\" height=\"{}\" \
+{}/>", num(w), num(h), fill)
+        });
+        svg
+    }
+
+    /// Writes the document.
+    pub fn write<W: Write + ?Sized>(self, out: &mut W) -> io::Result<()> {
+        let mut doc = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(doc, "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}pt\" height=\"{}pt\" \
+viewBox=\"0 0 {} {}\">", num(self.size.0), num(self.size.1), self.width,
+                         self.height);
+        if !self.figures.is_empty() {
+            doc.push_str("<defs>\n");
+            for (key, body) in self.figures.iter() {
+                let _ = write!(doc, "<g id=\"fig{}\">\n{}</g>\n", key, body);
+            }
+            doc.push_str("</defs>\n");
+        }
+        // pixel centres, on which the canvas draws, are half a pixel in
+        let _ = write!(doc, "<g transform=\"translate(0.5 0.5)\">\n{}</g>\n\
+</svg>\n", self.body);
+        out.write_all(doc.as_bytes())
+    }
+
+    /* Appends the element f makes out of the colour attributes of each
+       paint, fill or stroke. Nothing is drawn in a transparent colour. */
+    fn element<F>(&mut self, color: Rgba<u8>, paints: &[&str], f: F)
+        where F: FnOnce(&str) -> String {
+        if color[3] == 0 { return; }
+        let [r, g, b, a] = color.0;
+        let mut attrs = String::new();
+        for paint in paints {
+            let _ = write!(attrs, "{}=\"#{:02x}{:02x}{:02x}\" ", paint, r, g,
+                           b);
+            if a != 255 {
+                let _ = write!(attrs, "{}-opacity=\"{}\" ", paint,
+                               num(a as f64 / 255.0));
+            }
+        }
+        let _ = writeln!(self.body, "{}", f(&attrs));
+    }
+}
+
+impl Canvas for Svg {
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), width: f64,
+            color: Rgba<u8>) {
+        let width = num(width.max(1.0));
+        self.element(color, &["stroke"], |stroke| {
+            format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {}\
+stroke-width=\"{}\" stroke-linecap=\"square\"/>", num(from.0), num(from.1),
+                    num(to.0), num(to.1), stroke, width)
+        });
+    }
+
+    fn circle(&mut self, centre: (f64, f64), r: f64, thickness: f64,
+              color: Rgba<u8>) {
+        // the same ring as the raster one, from r - 1/2 to r + t - 1/2
+        let t = thickness.max(1.0);
+        let r = r + (t - 1.0) / 2.0;
+        self.element(color, &["stroke"], |stroke| {
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" {}\
+stroke-width=\"{}\"/>", num(centre.0), num(centre.1), num(r), stroke, num(t))
+        });
+    }
+
+    fn polygon(&mut self, points: &[(f64, f64)], color: Rgba<u8>) {
+        if points.is_empty() { return; }
+        let points: Vec<String> = points.iter()
+            .map(|p| format!("{},{}", num(p.0), num(p.1))).collect();
+        // edges included, as on the images
+        self.element(color, &["fill", "stroke"], |paint| {
+            format!("<polygon points=\"{}\" {}stroke-linejoin=\"round\"/>",
+                    points.join(" "), paint)
+        });
+    }
+
+    fn fill(&mut self, contours: &[Contour], color: Rgba<u8>) {
+        let mut d = String::new();
+        for c in contours.iter().filter(|c| !c.is_empty()) {
+            let _ = write!(d, "M{},{}", num(c[0].0), num(c[0].1));
+            for p in &c[1..] {
+                let _ = write!(d, "L{},{}", num(p.0), num(p.1));
+            }
+            d.push('Z');
+        }
+        self.element(color, &["fill"], |fill| {
+            format!("<path d=\"{}\" {}/>", d, fill)
+        });
+    }
+
+    fn figure(&mut self, key: u32, x: i32, y: i32, _reach: i32,
+              draw: &dyn Fn(&mut dyn Canvas)) {
+        if !self.figures.contains_key(&key) {
+            let body = std::mem::take(&mut self.body);
+            draw(self);
+            let figure = std::mem::replace(&mut self.body, body);
+            self.figures.insert(key, figure);
+        }
+        let _ = writeln!(self.body, "<use xlink:href=\"#fig{}\" x=\"{}\" \
+y=\"{}\"/>", key, x, y);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn figures_are_defined_once() {
+        let mut svg = Svg::new(100, 50, (75.0, 37.5), Rgba([0, 0, 0, 0]));
+        for x in [20, 60] {
+            svg.figure(1, x, 25, 10, &|c: &mut dyn Canvas| {
+                c.polygon(&[(0.0, 0.0), (5.0, 5.0)], Rgba([255, 0, 0, 128]));
+            });
+        }
+        let mut out = Vec::new();
+        svg.write(&mut out).unwrap();
+        let doc = String::from_utf8(out).unwrap();
+
+        assert!(!doc.contains("<rect"));
+        assert_eq!(doc.matches("<polygon").count(), 1);
+        assert_eq!(doc.matches("xlink:href=\"#fig1\"").count(), 2);
+        assert!(doc.contains("fill-opacity=\"0.502\" stroke=\"#ff0000\" \
+stroke-opacity=\"0.502\""));
+        assert!(doc.ends_with("</svg>\n"));
+    }
+}